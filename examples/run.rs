@@ -0,0 +1,64 @@
+// Run EVM bytecode from the command line and print the result.
+//
+//     cargo run --example run -- <code-hex> [calldata-hex]
+//
+// For example, store 0x2a in memory and return it as a word:
+//
+//     cargo run --example run -- 602a60005260206000f3
+
+use evm_rust::evm::context::ExecutionContext;
+use evm_rust::evm::interpreter::run;
+use std::env;
+use std::process;
+
+// Execute the program and render its return data, final stack and gas used
+fn run_hex(code_hex: &str, calldata_hex: &str) -> Result<String, String> {
+    let mut ctx = ExecutionContext::from_hex([0u8; 20], code_hex, calldata_hex)
+        .map_err(|err| format!("invalid hex: {}", err))?;
+    let status = match run(&mut ctx) {
+        Ok(()) => "success".to_string(),
+        Err(err) => format!("error: {}", err),
+    };
+    Ok(format!(
+        "status:      {}\nreturn data: 0x{}\nstack:       {:?}\ngas used:    {}",
+        status,
+        hex::encode(ctx.return_data()),
+        ctx.stack(),
+        ctx.gas().used(),
+    ))
+}
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    if args.is_empty() || args.len() > 2 {
+        eprintln!("usage: run <code-hex> [calldata-hex]");
+        process::exit(2);
+    }
+    let calldata = args.get(1).map_or("", String::as_str);
+    match run_hex(&args[0], calldata) {
+        Ok(report) => println!("{}", report),
+        Err(err) => {
+            eprintln!("{}", err);
+            process::exit(1);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reports_return_data_stack_and_gas() {
+        // Return 0x2a as a word, leaving 7 on the stack
+        let report = run_hex("6007602a60005260206000f3", "").unwrap();
+        let expected = [
+            "status:      success",
+            "return data: 0x000000000000000000000000000000000000000000000000000000000000002a",
+            "stack:       [0x7]",
+            "gas used:    21",
+        ];
+        assert_eq!(report, expected.join("\n"));
+        assert!(run_hex("60", "zz").is_err());
+    }
+}
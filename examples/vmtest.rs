@@ -0,0 +1,65 @@
+// Run VMTests JSON files in the ethereum/tests format and report each test.
+//
+//     cargo run --example vmtest --features vmtests -- [file.json ...]
+//
+// With no arguments, runs the fixtures checked in under fixtures/vmtests.
+
+use evm_rust::testing::vmtest::{load_vm_tests, run_vm_test};
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+use std::process;
+
+// Paths given on the command line, or every fixture file
+fn test_files() -> Result<Vec<PathBuf>, String> {
+    let args: Vec<PathBuf> = env::args().skip(1).map(PathBuf::from).collect();
+    if !args.is_empty() {
+        return Ok(args);
+    }
+    let dir = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("fixtures/vmtests");
+    let entries = fs::read_dir(&dir).map_err(|err| format!("{}: {}", dir.display(), err))?;
+    let mut files: Vec<PathBuf> = entries
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| path.extension().is_some_and(|ext| ext == "json"))
+        .collect();
+    files.sort();
+    Ok(files)
+}
+
+fn main() {
+    let files = match test_files() {
+        Ok(files) => files,
+        Err(err) => {
+            eprintln!("{}", err);
+            process::exit(2);
+        }
+    };
+    
+    let mut failures = 0;
+    for file in files {
+        let tests = fs::read_to_string(&file)
+            .map_err(|err| err.to_string())
+            .and_then(|json| load_vm_tests(&json).map_err(|err| err.to_string()));
+        let tests = match tests {
+            Ok(tests) => tests,
+            Err(err) => {
+                eprintln!("{}: {}", file.display(), err);
+                failures += 1;
+                continue;
+            }
+        };
+        for (name, test) in &tests {
+            match run_vm_test(test) {
+                Ok(()) => println!("ok    {}", name),
+                Err(err) => {
+                    println!("FAIL  {}: {}", name, err);
+                    failures += 1;
+                }
+            }
+        }
+    }
+    if failures > 0 {
+        eprintln!("{} failed", failures);
+        process::exit(1);
+    }
+}
@@ -0,0 +1,48 @@
+use crate::evm::context::Address;
+use primitive_types::U256;
+use crate::evm::collections::Set;
+
+// Addresses and storage slots already touched in this transaction (EIP-2929).
+// Anything not in here is cold and costs more on first access
+#[derive(Clone, Default)]
+pub struct AccessList {
+    addresses: Set<Address>,
+    slots: Set<(Address, U256)>,
+}
+
+impl AccessList {
+
+    pub fn new() -> Self {
+        AccessList {
+            addresses: Set::new(),
+            slots: Set::new(),
+        }
+    }
+
+    pub fn is_warm_address(&self, address: &Address) -> bool {
+        self.addresses.contains(address)
+    }
+
+    pub fn is_warm_slot(&self, address: &Address, slot: U256) -> bool {
+        self.slots.contains(&(*address, slot))
+    }
+
+    // Mark an address warm, returning whether it already was
+    pub fn warm_address(&mut self, address: Address) -> bool {
+        !self.addresses.insert(address)
+    }
+
+    // Mark a storage slot warm, returning whether it already was
+    pub fn warm_slot(&mut self, address: Address, slot: U256) -> bool {
+        !self.slots.insert((address, slot))
+    }
+
+    // Make an address cold again, for undoing an access
+    pub fn cool_address(&mut self, address: &Address) {
+        self.addresses.remove(address);
+    }
+
+    pub fn cool_slot(&mut self, address: Address, slot: U256) {
+        self.slots.remove(&(address, slot));
+    }
+}
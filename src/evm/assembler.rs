@@ -0,0 +1,237 @@
+use crate::evm::collections::Map;
+use crate::evm::disasm::mnemonic;
+use crate::evm::opcodes::*;
+use alloc::{string::{String, ToString}, vec::Vec};
+use core::fmt;
+use primitive_types::U256;
+
+// Why a line of assembly could not be turned into bytecode. Lines are
+// numbered from 1
+#[derive(Debug, PartialEq, Eq)]
+pub enum AssembleError {
+    UnknownMnemonic { line: usize, mnemonic: String },
+    // A PUSH1..PUSH32 without its immediate
+    MissingImmediate { line: usize },
+    // An operand on an instruction that takes none
+    UnexpectedOperand { line: usize },
+    // An operand that is not a hex (0x...) or decimal number
+    InvalidImmediate { line: usize, operand: String },
+    // An immediate with more bytes than the PUSH holds
+    ImmediateTooWide { line: usize, width: usize },
+    // A hex immediate with fewer bytes than the PUSH holds
+    ImmediateTooShort { line: usize, width: usize },
+    // A label definition that is not an identifier
+    InvalidLabel { line: usize, label: String },
+    UndefinedLabel { line: usize, label: String },
+    DuplicateLabel { line: usize, label: String },
+}
+
+impl fmt::Display for AssembleError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AssembleError::UnknownMnemonic { line, mnemonic } => write!(f, "line {}: unknown mnemonic {}", line, mnemonic),
+            AssembleError::MissingImmediate { line } => write!(f, "line {}: missing push immediate", line),
+            AssembleError::UnexpectedOperand { line } => write!(f, "line {}: instruction takes no operand", line),
+            AssembleError::InvalidImmediate { line, operand } => write!(f, "line {}: invalid immediate {}", line, operand),
+            AssembleError::ImmediateTooWide { line, width } => write!(f, "line {}: immediate does not fit in {} bytes", line, width),
+            AssembleError::ImmediateTooShort { line, width } => write!(f, "line {}: hex immediate must be {} bytes", line, width),
+            AssembleError::InvalidLabel { line, label } => write!(f, "line {}: invalid label {}", line, label),
+            AssembleError::UndefinedLabel { line, label } => write!(f, "line {}: undefined label {}", line, label),
+            AssembleError::DuplicateLabel { line, label } => write!(f, "line {}: label {} is already defined", line, label),
+        }
+    }
+}
+
+impl core::error::Error for AssembleError {}
+
+// Turn assembly into bytecode: one instruction per line, written the way
+// disassemble prints them (`PUSH1 0x01`, `ADD`). Hex immediates must be as
+// wide as the PUSH, so a PUSH that disassemble shows cut off by the end of
+// the code is rejected rather than padded into different bytes. Immediates
+// may also be decimal, which is left-padded. `INVALID 0xNN` emits the raw
+// byte, so the disassembly of any code without a truncated PUSH assembles
+// back to that code. Mnemonics are case-insensitive and anything after `;`
+// is a comment.
+//
+// `name:` defines a label at the offset of the next instruction, and a PUSH
+// may take a label in place of a number. The PUSH keeps the width it is
+// written with, so labels can be used before they are defined: the first
+// pass emits zeros and records where each label goes, and once every label
+// is known the second pass patches in the offsets
+pub fn assemble(source: &str) -> Result<Vec<u8>, AssembleError> {
+    let mut code = Vec::new();
+    let mut labels: Map<String, usize> = Map::new();
+    // (offset of the immediate, its width, label, line) for each label use
+    let mut fixups = Vec::new();
+    for (index, line) in source.lines().enumerate() {
+        let line_number = index + 1;
+        let line = line.split(';').next().unwrap_or_default();
+        let mut tokens = line.split_whitespace().peekable();
+        // Any number of labels may come before the instruction
+        while let Some(label) = tokens.peek().and_then(|token| token.strip_suffix(':')) {
+            if !is_label(label) {
+                return Err(AssembleError::InvalidLabel { line: line_number, label: label.to_string() });
+            }
+            if labels.insert(label.to_string(), code.len()).is_some() {
+                return Err(AssembleError::DuplicateLabel { line: line_number, label: label.to_string() });
+            }
+            tokens.next();
+        }
+        let Some(name) = tokens.next() else {
+            continue;
+        };
+        let operand = tokens.next();
+        if tokens.next().is_some() {
+            return Err(AssembleError::UnexpectedOperand { line: line_number });
+        }
+        
+        let name = name.to_ascii_uppercase();
+        let opcode = opcode_for(&name).ok_or_else(|| AssembleError::UnknownMnemonic {
+            line: line_number,
+            mnemonic: name.clone(),
+        })?;
+        match (opcode, operand) {
+            (PUSH1..=PUSH32, Some(operand)) if is_label(operand) => {
+                let width = (opcode - PUSH1 + 1) as usize;
+                code.push(opcode);
+                fixups.push((code.len(), width, operand, line_number));
+                code.resize(code.len() + width, 0);
+            }
+            (PUSH1..=PUSH32, Some(operand)) => {
+                let width = (opcode - PUSH1 + 1) as usize;
+                code.push(opcode);
+                code.extend_from_slice(&parse_immediate(operand, width, line_number)?);
+            }
+            (PUSH1..=PUSH32, None) => return Err(AssembleError::MissingImmediate { line: line_number }),
+            // An undefined byte, as disassemble renders it
+            (INVALID, Some(operand)) => code.extend_from_slice(&parse_immediate(operand, 1, line_number)?),
+            (_, Some(_)) => return Err(AssembleError::UnexpectedOperand { line: line_number }),
+            (_, None) => code.push(opcode),
+        }
+    }
+    
+    for (offset, width, label, line) in fixups {
+        let target = *labels.get(label).ok_or_else(|| AssembleError::UndefinedLabel { line, label: label.to_string() })?;
+        let target = U256::from(target);
+        if target.bits() > 8 * width {
+            return Err(AssembleError::ImmediateTooWide { line, width });
+        }
+        code[offset..offset + width].copy_from_slice(&target.to_big_endian()[32 - width..]);
+    }
+    Ok(code)
+}
+
+// Labels are identifiers: a letter or underscore, then letters, digits and
+// underscores
+fn is_label(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars.next().is_some_and(|first| first.is_ascii_alphabetic() || first == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+fn opcode_for(name: &str) -> Option<u8> {
+    (0..=u8::MAX).find(|&opcode| mnemonic(opcode) == Some(name))
+}
+
+// The immediate as exactly `width` big-endian bytes. Hex counts its digits,
+// so 0x0001 is two bytes wide even though its value would fit in one, and
+// must fill the width exactly
+fn parse_immediate(operand: &str, width: usize, line: usize) -> Result<Vec<u8>, AssembleError> {
+    let invalid = || AssembleError::InvalidImmediate { line, operand: operand.to_string() };
+    let (value, bytes) = match operand.strip_prefix("0x").or_else(|| operand.strip_prefix("0X")) {
+        Some(digits) => {
+            if digits.is_empty() || digits.len() > 64 {
+                return Err(invalid());
+            }
+            let value = U256::from_str_radix(digits, 16).map_err(|_| invalid())?;
+            let bytes = digits.len().div_ceil(2);
+            if bytes < width {
+                return Err(AssembleError::ImmediateTooShort { line, width });
+            }
+            (value, bytes)
+        }
+        None => {
+            if !operand.bytes().all(|byte| byte.is_ascii_digit()) {
+                return Err(invalid());
+            }
+            let value = U256::from_dec_str(operand).map_err(|_| invalid())?;
+            (value, value.bits().div_ceil(8))
+        }
+    };
+    if bytes > width {
+        return Err(AssembleError::ImmediateTooWide { line, width });
+    }
+    Ok(value.to_big_endian()[32 - width..].to_vec())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::evm::context::ExecutionContext;
+    use crate::evm::disasm::disassemble;
+    use crate::evm::interpreter::run;
+    use primitive_types::U256;
+    use alloc::{format, vec};
+
+    // Join a disassembly back into assembly source
+    fn source(code: &[u8]) -> String {
+        disassemble(code).into_iter().map(|(_, line)| format!("{}\n", line)).collect()
+    }
+
+    #[test]
+    fn disassembly_round_trips() {
+        let mut code = vec![PUSH1, 0x01, PUSH2, 0x00, 0xff, ADD, 0x0c, PUSH32, 0x5b];
+        code.extend([0u8; 31]);
+        code.extend([JUMPDEST, STOP]);
+        assert_eq!(assemble(&source(&code)).unwrap(), code);
+    }
+
+    #[test]
+    fn truncated_push_is_rejected() {
+        // PUSH1 0x01 then a PUSH2 with only one immediate byte
+        let code = [PUSH1, 0x01, PUSH2, 0xff];
+        assert_eq!(source(&code), "PUSH1 0x01\nPUSH2 0xff\n");
+        assert_eq!(assemble(&source(&code)), Err(AssembleError::ImmediateTooShort { line: 2, width: 2 }));
+    }
+
+    #[test]
+    fn decimal_immediates_are_padded() {
+        assert_eq!(assemble("PUSH2 255").unwrap(), vec![PUSH2, 0x00, 0xff]);
+        assert_eq!(assemble("PUSH1 256"), Err(AssembleError::ImmediateTooWide { line: 1, width: 1 }));
+    }
+
+    #[test]
+    fn labelled_loop_runs() {
+        // Sum 5 + 4 + 3 + 2 + 1 with a countdown loop and store it in slot 0
+        let source = "
+            PUSH1 5          ; counter
+            PUSH0            ; sum
+        loop: JUMPDEST       ; [sum, counter]
+            DUP2
+            ADD              ; [sum + counter, counter]
+            SWAP1
+            PUSH1 1
+            SWAP1
+            SUB              ; [counter - 1, sum]
+            SWAP1
+            DUP2             ; [counter, sum, counter]
+            PUSH2 loop
+            JUMPI
+            PUSH2 done
+            JUMP
+            INVALID
+        done:
+            JUMPDEST
+            PUSH0
+            SSTORE
+        ";
+        let code = assemble(source).unwrap();
+        assert_eq!(code[3], JUMPDEST);
+        assert_eq!(code[13..16], [PUSH2, 0, 3]);
+        let mut ctx = ExecutionContext::new([0u8; 20], code, vec![]);
+        run(&mut ctx).unwrap();
+        assert_eq!(ctx.storage().load(U256::zero()).unwrap(), U256::from(15));
+
+        assert_eq!(assemble("PUSH1 nowhere"), Err(AssembleError::UndefinedLabel { line: 1, label: "nowhere".into() }));
+    }
+}
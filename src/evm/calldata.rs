@@ -1,21 +1,34 @@
-use memory::Memory;
-use stack::Stack; 
+use crate::evm::memory::Memory;
+use crate::evm::opcodes::padded_slice;
 use primitive_types::U256;
+use core::fmt;
+use alloc::vec::Vec;
 
 
 pub struct Calldata {
     data: Vec<u8>,
 }
 
+#[derive(Debug)]
 pub enum CalldataError {
     InvalidCalldataAccess, 
 }
 
+impl fmt::Display for CalldataError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CalldataError::InvalidCalldataAccess => write!(f, "invalid calldata access"),
+        }
+    }
+}
+
+impl core::error::Error for CalldataError {}
+
 impl Calldata {
 
     pub fn new(data: Vec<u8>) -> Self {
         Calldata {
-            data: data
+            data
         }
     }
 
@@ -23,38 +36,70 @@ impl Calldata {
         self.data.len()
     }
 
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.data
+    }
+
+    // length bytes starting at offset, zero-padded past the end
+    pub fn slice(&self, offset: usize, length: usize) -> Vec<u8> {
+        padded_slice(&self.data, offset, length)
+    }
+
     pub fn load(&self, offset: usize) -> U256 {
-        // CALLDATALOAD loads 32 bytes starting at offset
+        self.load_with_len(offset).0
+    }
+
+    // CALLDATALOAD's 32-byte word at offset, zero-padded past the end, along
+    // with how many of its bytes actually came from the calldata
+    pub fn load_with_len(&self, offset: usize) -> (U256, usize) {
         let mut bytes = [0u8; 32];
-        for i in 0..32 {
-            if offset + i < self.data.len() {
-                bytes[i] = self.data[offset + i];
-            } else {
-                bytes[i] = 0;
-            }
+        let available = self.data.len().saturating_sub(offset).min(32);
+        if available > 0 {
+            bytes[..available].copy_from_slice(&self.data[offset..offset + available]);
         }
-        U256::from_big_endian(&bytes)
-    }
-
-    pub fn copy_to_memory(&self, calldata_offset: usize, memory_offset: usize, length: usize, memory: &mut memory::Memory) -> Result<(), CalldataError> {
-        // Copy calldata to memory
-        let mut bytes = Vec::new();
-        for i in 0..length {
-            let byte = if calldata_offset + i < self.data.len() {
-                self.data[calldata_offset + i]
-            } else {
-                0
-            };
-            bytes.push(byte);
-        }
-        memory.store_bytes(memory_offset, &bytes);
-        Ok(())
+        (U256::from_big_endian(&bytes), available)
+    }
+
+    pub fn copy_to_memory(&self, calldata_offset: usize, memory_offset: usize, length: usize, memory: &mut Memory) -> Result<(), CalldataError> {
+        let bytes = self.slice(calldata_offset, length);
+        memory.store_range(memory_offset, &bytes).map_err(|_| CalldataError::InvalidCalldataAccess)
+    }
+
+    // Unlike load, reading past the end is an error rather than padding
+    pub fn read_byte(&self, offset: usize) -> Result<u8, CalldataError> {
+        self.data.get(offset).copied().ok_or(CalldataError::InvalidCalldataAccess)
     }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-    fn read_byte(&self, offset: usize) -> Result<u8, CalldataError> {
-        if offset >= self.data.len() {
-            return Ok(0); // default value for out-of-bounds access
+    #[test]
+    fn load_with_len_counts_real_bytes() {
+        let calldata = Calldata::new((1..=40).collect());
+        let (word, len) = calldata.load_with_len(30);
+        assert_eq!(len, 10);
+        let mut expected = [0u8; 32];
+        for (i, byte) in expected[..10].iter_mut().enumerate() {
+            *byte = 31 + i as u8;
         }
-        Ok(self.data[offset])
+        assert_eq!(word, U256::from_big_endian(&expected));
+        assert_eq!(calldata.load(30), word);
+        assert_eq!(calldata.load_with_len(0).1, 32);
+        assert_eq!(calldata.load_with_len(100), (U256::zero(), 0));
+        // Padding is not a real byte
+        assert_eq!(calldata.read_byte(39).unwrap(), 40);
+        assert!(calldata.read_byte(40).is_err());
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn slice_zero_pads() {
+        let calldata = Calldata::new(alloc::vec![1, 2, 3]);
+        assert_eq!(calldata.as_bytes(), &[1, 2, 3]);
+        assert_eq!(calldata.slice(1, 4), [2, 3, 0, 0]);
+        assert_eq!(calldata.slice(5, 2), [0, 0]);
+        assert_eq!(calldata.slice(usize::MAX, 1), [0]);
+        assert!(calldata.slice(0, 0).is_empty());
+    }
+}
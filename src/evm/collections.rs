@@ -0,0 +1,8 @@
+// Maps and sets used for storage and bookkeeping. With std they are the
+// hash-based collections; without it they fall back to the ordered ones in
+// alloc, which only need the keys to be comparable
+#[cfg(feature = "std")]
+pub use std::collections::{HashMap as Map, HashSet as Set};
+
+#[cfg(not(feature = "std"))]
+pub use alloc::collections::{BTreeMap as Map, BTreeSet as Set};
@@ -1 +1,18 @@
-pub const MAX_DEPTH: usize = 1024;
\ No newline at end of file
+// Maximum number of items the EVM stack can hold
+pub const MAX_DEPTH: usize = 1024;
+
+// Maximum number of nested call frames; CALL and CREATE beyond it fail
+pub const CALL_DEPTH_LIMIT: usize = 1024;
+
+// Gas available to an execution unless the caller sets a limit
+pub const DEFAULT_GAS_LIMIT: u64 = 30_000_000;
+
+// Gas available to estimate_gas: far beyond any block's limit, but bounded
+// so that paying for memory expansion still caps how much can be allocated
+pub const ESTIMATE_GAS_LIMIT: u64 = 1 << 36;
+
+// Number of steps ExecutionContext::step remembers for step_back
+pub const STEP_HISTORY_LIMIT: usize = 1024;
+
+// Chain id reported by CHAINID unless configured otherwise (mainnet)
+pub const DEFAULT_CHAIN_ID: u64 = 1;
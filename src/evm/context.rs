@@ -1,35 +1,260 @@
-use stack::Stack;
-use memory::Memory;
-use calldata::Calldata;
-use primitive_types::U256; 
-use storage::Storage;
+use crate::evm::stack::Stack;
+use crate::evm::memory::{Memory, MemoryError};
+use crate::evm::calldata::Calldata;
+use crate::evm::storage::{Storage, TransientStorage};
+use crate::evm::state::WorldState;
+use crate::evm::access_list::AccessList;
+use crate::evm::env::{BlockEnv, TxEnv};
+use crate::evm::log::Log;
+use crate::evm::gas::{memory_cost, Gas};
+use crate::evm::hardfork::Hardfork;
+use crate::evm::history::{StepHistory, StepSnapshot};
+use crate::evm::opcodes::{valid_jumpdests, InstructionError, STOP};
+use crate::evm::interpreter::{begin_frame, end_frame, run, step, ExecutionError, FrameSnapshot, StepResult};
+use crate::evm::collections::{Map, Set};
+use core::fmt::Write;
+use primitive_types::U256;
+use crate::evm::constants::{DEFAULT_GAS_LIMIT, MAX_DEPTH};
+use alloc::{format, string::String, vec, vec::Vec};
 
 pub type Address = [u8; 20];
 
+// How a frame finished, or Running while it has not
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ExecStatus {
+    Running,
+    // Halted by RETURN
+    Returned,
+    // Halted by REVERT
+    Reverted,
+    // Halted by STOP, SELFDESTRUCT or running off the end of the code
+    Stopped,
+}
+
+// The parts of a frame that change as it executes, detached from its code
+// and environment so they can be saved and inspected
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ExecutionState {
+    pub pc: usize,
+    pub status: ExecStatus,
+    pub stack: Stack,
+    // Serialized as a hex string
+    #[cfg_attr(feature = "serde", serde(with = "hex_bytes"))]
+    pub memory: Vec<u8>,
+    pub storage: Storage,
+    pub gas_used: u64,
+    #[cfg_attr(feature = "serde", serde(with = "hex_bytes"))]
+    pub return_data: Vec<u8>,
+}
+
 pub struct ExecutionContext {
     code: Vec<u8>, 
+    jumpdests: Set<usize>, 
     stack: Stack, 
     memory: Memory, 
     calldata: Calldata, 
-    contractAddress: Address,
+    storage: Storage, 
+    // Transaction-scoped storage of every contract; a new context always
+    // starts with it empty
+    transient_storage: TransientStorage, 
+    contract_address: Address,
+    // Every account; the executing contract's storage lives in `storage`
+    // instead while its frame runs
+    state: WorldState, 
+    // Accounts that ran SELFDESTRUCT; they are removed once the outermost
+    // frame finishes
+    destroyed: Set<Address>, 
+    access_list: AccessList, 
+    // Value of each written slot at the start of the transaction, keyed by
+    // (contract, slot); recorded the first time the slot is written
+    original_storage: Map<(Address, U256), U256>, 
+    // Refund earned by SSTOREs in this frame; can dip below zero while a
+    // slot is being dirtied and restored
+    gas_refund: i64, 
+    tx_env: TxEnv, 
+    block_env: BlockEnv, 
+    // Rules the code runs under; inherited by every child frame
+    hardfork: Hardfork, 
+    gas: Gas, 
+    // Number of call frames above this one; zero for the top-level frame
+    depth: usize, 
+    // Set inside STATICCALL frames, where state changes are forbidden
+    static_mode: bool, 
+    // Maximum number of instructions a single run may execute
+    step_limit: Option<u64>, 
     pc: usize, 
     stopped: bool, 
+    status: ExecStatus, 
+    // A PUSH ran into the end of the code and had its immediate zero-padded
+    truncated_push: bool, 
     return_data: Vec<u8>,
+    logs: Vec<Log>,
+    // Steps taken with `step`, for undoing them with `step_back`
+    history: StepHistory,
+    // Set while a frame started by `step` has yet to finish
+    frame: Option<FrameSnapshot>,
 }
 
 impl ExecutionContext {
 
-    pub fn new(contractAddress: Address, code: Vec<u8>, calldata: Vec<u8>) -> Self {
+    pub fn new(contract_address: Address, code: Vec<u8>, calldata: Vec<u8>) -> Self {
         ExecutionContext {
+            jumpdests: valid_jumpdests(&code), 
             code,  
             stack: Stack::new(), 
             memory: Memory::new(), 
             calldata: Calldata::new(calldata),  
-            contractAddress, 
+            storage: Storage::new(), 
+            transient_storage: TransientStorage::new(), 
+            contract_address, 
+            state: WorldState::new(), 
+            destroyed: Set::new(), 
+            access_list: AccessList::new(), 
+            original_storage: Map::new(), 
+            gas_refund: 0, 
+            tx_env: TxEnv::default(), 
+            block_env: BlockEnv::default(), 
+            hardfork: Hardfork::default(), 
+            gas: Gas::new(DEFAULT_GAS_LIMIT), 
+            depth: 0, 
+            static_mode: false, 
+            step_limit: None, 
             pc: 0, 
             stopped: false, 
-            return_data: Vec::new() 
+            status: ExecStatus::Running, 
+            truncated_push: false, 
+            return_data: Vec::new(), 
+            logs: Vec::new(), 
+            history: StepHistory::default(), 
+            frame: None, 
+        }
+    }
+
+    // Build a context from hex-encoded code and calldata, each with an
+    // optional 0x prefix
+    pub fn from_hex(contract_address: Address, code_hex: &str, calldata_hex: &str) -> Result<Self, hex::FromHexError> {
+        let code = decode_hex(code_hex)?;
+        let calldata = decode_hex(calldata_hex)?;
+        Ok(Self::new(contract_address, code, calldata))
+    }
+
+    pub fn execution_state(&self) -> ExecutionState {
+        ExecutionState {
+            pc: self.pc,
+            status: self.status,
+            stack: self.stack.clone(),
+            // All of memory is always in range
+            memory: self.memory.load_range(0, self.memory.size()).unwrap_or_default(),
+            storage: self.storage.clone(),
+            gas_used: self.gas.used(),
+            return_data: self.return_data.clone(),
+        }
+    }
+
+    // Put a frame back into a saved state. The code and environment are
+    // left alone, so the state must come from a context running the same code
+    pub fn restore(&mut self, state: ExecutionState) {
+        self.pc = state.pc;
+        self.set_status(state.status);
+        self.stack = state.stack;
+        self.memory = Memory::new();
+        // A write at offset 0 cannot overflow
+        let _ = self.memory.store_range(0, &state.memory);
+        self.storage = state.storage;
+        self.gas = Gas::new(self.gas.limit());
+        if self.gas.charge(state.gas_used).is_err() {
+            self.gas.consume_all();
+        }
+        self.return_data = state.return_data;
+        self.truncated_push = false;
+        self.history.clear();
+        self.frame = None;
+    }
+
+    // Restore a saved state and keep running from its PC
+    pub fn resume(&mut self, state: ExecutionState) -> Result<(), ExecutionError> {
+        self.restore(state);
+        run(self)
+    }
+
+    // Render the frame for debugging: PC, status, stack (top first), memory
+    // as a hex dump and storage sorted by slot
+    pub fn dump(&self) -> String {
+        let mut out = String::new();
+        writeln!(out, "pc:      {}", self.pc).unwrap();
+        writeln!(out, "status:  {:?}", self.status).unwrap();
+        writeln!(out, "stack:   {:?}", self.stack).unwrap();
+        writeln!(out, "memory:\n{:?}", self.memory).unwrap();
+        
+        let mut slots: Vec<_> = self.storage.iter().collect();
+        slots.sort();
+        write!(out, "storage:").unwrap();
+        if slots.is_empty() {
+            write!(out, "\n<empty>").unwrap();
+        }
+        for (key, value) in slots {
+            write!(out, "\n{:#x}: {:#x}", key, value).unwrap();
+        }
+        out
+    }
+
+    // Execute exactly one instruction, for debuggers. Once the frame has
+    // stopped nothing runs and every further step reports it as halted
+    pub fn step(&mut self) -> Result<StepResult, ExecutionError> {
+        let pc_before = self.pc;
+        let opcode = self.code.get(pc_before).copied().unwrap_or(STOP);
+        let gas_before = self.gas.used();
+        if !self.stopped {
+            let mut snapshot = StepSnapshot::capture(self, opcode);
+            let frame = match self.frame.take() {
+                Some(frame) => frame,
+                None => begin_frame(self),
+            };
+            let result = step(self);
+            if result.is_ok() && !self.stopped {
+                self.frame = Some(frame);
+            } else {
+                snapshot.finish_frame(self, frame.clone());
+                end_frame(self, frame, result.is_err());
+            }
+            // Recorded even if the step fails, so a failed step can be undone
+            self.history.push(snapshot);
+            result.map_err(|kind| ExecutionError { kind, pc: pc_before, opcode })?;
         }
+        Ok(StepResult {
+            opcode,
+            pc_before,
+            pc_after: self.pc,
+            gas_used: self.gas.used() - gas_before,
+            halted: self.stopped,
+        })
+    }
+
+    // Undo the most recent step taken with `step`, returning false once
+    // there is nothing left to undo. Only the last STEP_HISTORY_LIMIT steps
+    // are remembered
+    pub fn step_back(&mut self) -> bool {
+        match self.history.pop() {
+            Some(snapshot) => {
+                snapshot.undo(self);
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub(crate) fn take_frame(&mut self) -> Option<FrameSnapshot> {
+        self.frame.take()
+    }
+
+    pub(crate) fn set_frame(&mut self, frame: Option<FrameSnapshot>) {
+        self.frame = frame;
+    }
+
+    pub fn builder() -> ExecutionContextBuilder {
+        ExecutionContextBuilder::default()
     }
 
     pub fn stack_mut(&mut self) -> &mut Stack {
@@ -48,45 +273,126 @@ impl ExecutionContext {
         &self.memory
     }
 
-    pub fn pc(&self) -> usize {
-        self.pc
+    pub fn storage_mut(&mut self) -> &mut Storage {
+        &mut self.storage
     }
 
-    pub fn increment_pc(&mut self) {
-        self.pc += 1; 
+    pub fn storage(&self) -> &Storage {
+        &self.storage
+    }
+
+    pub fn transient_storage_mut(&mut self) -> &mut TransientStorage {
+        &mut self.transient_storage
+    }
+
+    pub fn transient_storage(&self) -> &TransientStorage {
+        &self.transient_storage
+    }
+
+    pub fn gas(&self) -> &Gas {
+        &self.gas
+    }
+
+    pub fn gas_mut(&mut self) -> &mut Gas {
+        &mut self.gas
+    }
+
+    // Charge for and perform the memory growth needed to access
+    // [offset, offset + length). Zero-length accesses never expand memory,
+    // and a range that ends past usize::MAX is out of bounds
+    pub fn expand_memory(&mut self, offset: usize, length: usize) -> Result<(), InstructionError> {
+        if length == 0 {
+            return Ok(());
+        }
+        let end = offset.checked_add(length).ok_or(InstructionError::OutOfBounds)?;
+        let new_words = end.div_ceil(32) as u64;
+        let current_words = self.memory.word_count() as u64;
+        if new_words > current_words {
+            self.gas.charge(memory_cost(new_words) - memory_cost(current_words))?;
+            self.memory.expand(end);
+        }
+        Ok(())
+    }
+
+    pub fn pc(&self) -> usize {
+        self.pc
     }
 
     pub fn set_pc(&mut self, pc: usize) {
         self.pc = pc;
     }
 
+    // Move the PC past an instruction of n bytes. A PC that cannot be
+    // represented is reported as out of bounds rather than wrapping
+    pub fn advance_pc(&mut self, n: usize) -> Result<(), InstructionError> {
+        self.pc = self.pc.checked_add(n).ok_or(InstructionError::OutOfBounds)?;
+        Ok(())
+    }
+
+    // Read num_bytes of code starting at the PC. Bytes past the end of the
+    // code read as zero, which is how the EVM pads a truncated PUSH immediate
     pub fn read_code(&self, num_bytes: usize) -> Vec<u8> {
-        let mut bytes = Vec::new(); 
-        for i in 0..num_bytes {
-            if self.pc + i < self.code.len() {
-                bytes.push(self.code[self.pc + i]); 
-            } else {
-                bytes.push(0); 
-            }
+        self.read_code_with_len(num_bytes).0
+    }
+
+    // Same as read_code, along with how many of the bytes are real code
+    // rather than padding
+    pub fn read_code_with_len(&self, num_bytes: usize) -> (Vec<u8>, usize) {
+        let mut bytes = vec![0u8; num_bytes];
+        let available = self.code.len().saturating_sub(self.pc).min(num_bytes);
+        if available > 0 {
+            bytes[..available].copy_from_slice(&self.code[self.pc..self.pc + available]);
         }
-        bytes
+        (bytes, available)
+    }
+
+    // Whether a PUSH immediate ran past the end of the code. Its missing
+    // bytes read as zero, which is valid but usually means malformed code
+    pub fn truncated_push(&self) -> bool {
+        self.truncated_push
+    }
+
+    pub fn set_truncated_push(&mut self, truncated_push: bool) {
+        self.truncated_push = truncated_push;
     }
 
     pub fn code(&self) -> &Vec<u8> {
         &self.code
     }
 
+    pub fn is_valid_jumpdest(&self, dest: usize) -> bool {
+        self.jumpdests.contains(&dest)
+    }
+
     pub fn stopped(&self) -> bool {
         self.stopped
     }
 
     pub fn stop(&mut self) {
-        self.stopped = true; 
+        self.set_status(ExecStatus::Stopped);
+    }
+
+    pub fn status(&self) -> ExecStatus {
+        self.status
+    }
+
+    // Any status other than Running also marks the frame stopped
+    pub fn set_status(&mut self, status: ExecStatus) {
+        self.status = status;
+        self.stopped = status != ExecStatus::Running;
     }
 
-    pub fn set_return_data(&mut self, offset: usize, length: usize) -> Result<()> {
+    // Take the return data from memory; the range must already have been
+    // expanded into, except that an empty range may name any offset
+    pub fn set_return_data(&mut self, offset: usize, length: usize) -> Result<(), MemoryError> {
+        if length > 0 {
+            let end = offset.checked_add(length).ok_or(MemoryError::MemoryOverflow)?;
+            if end > self.memory.size() {
+                return Err(MemoryError::MemoryAccessError);
+            }
+        }
+        self.return_data = self.memory.load_range(offset, length)?;
         self.stopped = true; 
-        self.return_data = self.memory.load_range(offset, length);
         Ok(())
     }
 
@@ -94,8 +400,142 @@ impl ExecutionContext {
         &self.return_data
     }
 
+    pub fn return_data_mut(&mut self) -> &mut Vec<u8> {
+        &mut self.return_data
+    }
+
+    // The return data as a single ABI word, if it is exactly one
+    pub fn return_data_as_u256(&self) -> Option<U256> {
+        if self.return_data.len() != 32 {
+            return None;
+        }
+        Some(U256::from_big_endian(&self.return_data))
+    }
+
+    // The return data as an ABI-encoded address: one word whose top 12
+    // bytes are zero
+    pub fn return_data_as_address(&self) -> Option<Address> {
+        if self.return_data.len() != 32 || self.return_data[..12].iter().any(|&byte| byte != 0) {
+            return None;
+        }
+        let mut address = [0u8; 20];
+        address.copy_from_slice(&self.return_data[12..]);
+        Some(address)
+    }
+
+    // The return data as 0x-prefixed hex
+    pub fn return_data_hex(&self) -> String {
+        format!("0x{}", hex::encode(&self.return_data))
+    }
+
+    pub fn depth(&self) -> usize {
+        self.depth
+    }
+
+    pub fn set_depth(&mut self, depth: usize) {
+        self.depth = depth;
+    }
+
+    pub fn step_limit(&self) -> Option<u64> {
+        self.step_limit
+    }
+
+    pub fn set_step_limit(&mut self, step_limit: Option<u64>) {
+        self.step_limit = step_limit;
+    }
+
+    pub fn static_mode(&self) -> bool {
+        self.static_mode
+    }
+
+    pub fn set_static_mode(&mut self, static_mode: bool) {
+        self.static_mode = static_mode;
+    }
+
+    pub fn logs(&self) -> &Vec<Log> {
+        &self.logs
+    }
+
+    pub fn logs_mut(&mut self) -> &mut Vec<Log> {
+        &mut self.logs
+    }
+
     pub fn contract_address(&self) -> &Address {
-        &self.contractAddress
+        &self.contract_address
+    }
+
+    pub fn state(&self) -> &WorldState {
+        &self.state
+    }
+
+    pub fn state_mut(&mut self) -> &mut WorldState {
+        &mut self.state
+    }
+
+    pub fn access_list(&self) -> &AccessList {
+        &self.access_list
+    }
+
+    pub fn access_list_mut(&mut self) -> &mut AccessList {
+        &mut self.access_list
+    }
+
+    pub fn original_storage(&self) -> &Map<(Address, U256), U256> {
+        &self.original_storage
+    }
+
+    pub fn original_storage_mut(&mut self) -> &mut Map<(Address, U256), U256> {
+        &mut self.original_storage
+    }
+
+    pub fn gas_refund(&self) -> i64 {
+        self.gas_refund
+    }
+
+    pub fn add_gas_refund(&mut self, amount: i64) {
+        self.gas_refund += amount;
+    }
+
+    pub fn destroyed(&self) -> &Set<Address> {
+        &self.destroyed
+    }
+
+    pub fn destroyed_mut(&mut self) -> &mut Set<Address> {
+        &mut self.destroyed
+    }
+
+    // Delete the code, storage, balance and nonce of every self-destructed account
+    pub fn remove_destroyed_accounts(&mut self) {
+        for address in &self.destroyed {
+            if *address == self.contract_address {
+                self.storage = Storage::new();
+            }
+            self.state.remove_account(address);
+        }
+    }
+
+    pub fn tx_env(&self) -> &TxEnv {
+        &self.tx_env
+    }
+
+    pub fn tx_env_mut(&mut self) -> &mut TxEnv {
+        &mut self.tx_env
+    }
+
+    pub fn block_env(&self) -> &BlockEnv {
+        &self.block_env
+    }
+
+    pub fn block_env_mut(&mut self) -> &mut BlockEnv {
+        &mut self.block_env
+    }
+
+    pub fn hardfork(&self) -> Hardfork {
+        self.hardfork
+    }
+
+    pub fn set_hardfork(&mut self, hardfork: Hardfork) {
+        self.hardfork = hardfork;
     }
 
     // Calldata accessors
@@ -107,4 +547,360 @@ impl ExecutionContext {
         &mut self.calldata
     }
 
+    // Borrow calldata and memory together for copies between the two
+    pub fn calldata_and_memory_mut(&mut self) -> (&Calldata, &mut Memory) {
+        (&self.calldata, &mut self.memory)
+    }
+
+}
+
+// Chainable setup for contexts that need more than code and calldata;
+// anything not set keeps the default `ExecutionContext::new` would use
+pub struct ExecutionContextBuilder {
+    contract_address: Address, 
+    code: Vec<u8>, 
+    calldata: Vec<u8>, 
+    tx_env: TxEnv, 
+    block_env: BlockEnv, 
+    hardfork: Hardfork, 
+    gas_limit: u64, 
+    step_limit: Option<u64>, 
+    max_stack_depth: usize, 
+    state: WorldState, 
+}
+
+impl Default for ExecutionContextBuilder {
+    fn default() -> Self {
+        ExecutionContextBuilder {
+            contract_address: [0u8; 20], 
+            code: Vec::new(), 
+            calldata: Vec::new(), 
+            tx_env: TxEnv::default(), 
+            block_env: BlockEnv::default(), 
+            hardfork: Hardfork::default(), 
+            gas_limit: DEFAULT_GAS_LIMIT, 
+            step_limit: None, 
+            max_stack_depth: MAX_DEPTH, 
+            state: WorldState::new(), 
+        }
+    }
+}
+
+impl ExecutionContextBuilder {
+
+    pub fn address(mut self, address: Address) -> Self {
+        self.contract_address = address;
+        self
+    }
+
+    pub fn code(mut self, code: Vec<u8>) -> Self {
+        self.code = code;
+        self
+    }
+
+    pub fn calldata(mut self, calldata: Vec<u8>) -> Self {
+        self.calldata = calldata;
+        self
+    }
+
+    pub fn caller(mut self, caller: Address) -> Self {
+        self.tx_env.caller = caller;
+        self
+    }
+
+    pub fn origin(mut self, origin: Address) -> Self {
+        self.tx_env.origin = origin;
+        self
+    }
+
+    pub fn value(mut self, value: U256) -> Self {
+        self.tx_env.value = value;
+        self
+    }
+
+    pub fn gas_price(mut self, gas_price: U256) -> Self {
+        self.tx_env.gas_price = gas_price;
+        self
+    }
+
+    pub fn gas_limit(mut self, gas_limit: u64) -> Self {
+        self.gas_limit = gas_limit;
+        self
+    }
+
+    pub fn step_limit(mut self, step_limit: u64) -> Self {
+        self.step_limit = Some(step_limit);
+        self
+    }
+
+    // Limit the stack of this frame and any it calls to max_stack_depth items
+    pub fn max_stack_depth(mut self, max_stack_depth: usize) -> Self {
+        self.max_stack_depth = max_stack_depth;
+        self
+    }
+
+    pub fn block(mut self, block_env: BlockEnv) -> Self {
+        self.block_env = block_env;
+        self
+    }
+
+    pub fn state(mut self, state: WorldState) -> Self {
+        self.state = state;
+        self
+    }
+
+    pub fn hardfork(mut self, hardfork: Hardfork) -> Self {
+        self.hardfork = hardfork;
+        self
+    }
+
+    pub fn build(self) -> ExecutionContext {
+        let mut ctx = ExecutionContext::new(self.contract_address, self.code, self.calldata);
+        ctx.tx_env = self.tx_env;
+        ctx.block_env = self.block_env;
+        ctx.hardfork = self.hardfork;
+        ctx.gas = Gas::new(self.gas_limit);
+        ctx.step_limit = self.step_limit;
+        ctx.stack = Stack::with_max_depth(self.max_stack_depth);
+        ctx.state = self.state;
+        ctx
+    }
+}
+
+#[cfg(feature = "serde")]
+mod hex_bytes {
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(bytes: &[u8], serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&hex::encode(bytes))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<u8>, D::Error> {
+        let encoded = String::deserialize(deserializer)?;
+        hex::decode(encoded).map_err(serde::de::Error::custom)
+    }
+}
+
+fn decode_hex(input: &str) -> Result<Vec<u8>, hex::FromHexError> {
+    let input = input.strip_prefix("0x").unwrap_or(input);
+    hex::decode(input)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::evm::opcodes::{ADD, ADDRESS, BALANCE, INVALID, MSTORE, MSTORE8, PUSH0, PUSH1, PUSH2, RETURN, REVERT, SSTORE};
+
+    #[test]
+    fn resumed_run_matches_a_straight_run() {
+        let code = vec![PUSH1, 2, PUSH1, 3, ADD, PUSH1, 0, SSTORE, PUSH1, 7, PUSH1, 0, MSTORE, PUSH1, 32, PUSH1, 0, RETURN];
+        let mut straight = ExecutionContext::new([0u8; 20], code.clone(), vec![]);
+        run(&mut straight).unwrap();
+        
+        let mut first = ExecutionContext::new([0u8; 20], code.clone(), vec![]);
+        for _ in 0..5 {
+            first.step().unwrap();
+        }
+        let mut second = ExecutionContext::new([0u8; 20], code, vec![]);
+        second.resume(first.execution_state()).unwrap();
+        assert_eq!(second.execution_state(), straight.execution_state());
+        assert_eq!(second.status(), ExecStatus::Returned);
+    }
+
+    #[test]
+    fn restore_keeps_how_the_frame_ended() {
+        let mut ctx = ExecutionContext::new([0u8; 20], vec![PUSH1, 1, PUSH1, 0, REVERT], vec![]);
+        assert!(run(&mut ctx).is_err());
+        let state = ctx.execution_state();
+        assert_eq!(state.status, ExecStatus::Reverted);
+        
+        let mut restored = ExecutionContext::new([0u8; 20], vec![PUSH1, 1, PUSH1, 0, REVERT], vec![]);
+        restored.restore(state);
+        assert_eq!(restored.status(), ExecStatus::Reverted);
+        assert!(restored.stopped());
+        assert_eq!(restored.return_data(), &vec![0u8]);
+    }
+
+    #[test]
+    fn step_reports_each_instruction() {
+        let mut ctx = ExecutionContext::new([0u8; 20], vec![PUSH1, 2, PUSH1, 3, ADD, STOP], vec![]);
+        let expected = [(2, 3, 1), (4, 6, 2), (5, 9, 1), (5, 9, 1)];
+        for (pc, gas, stack_len) in expected {
+            ctx.step().unwrap();
+            assert_eq!(ctx.pc(), pc);
+            assert_eq!(ctx.gas().used(), gas);
+            assert_eq!(ctx.stack().len(), stack_len);
+        }
+        assert_eq!(ctx.stack().peek(0).unwrap(), U256::from(5));
+        assert_eq!(ctx.status(), ExecStatus::Stopped);
+    }
+
+    #[test]
+    fn step_charges_what_run_charges() {
+        let code = vec![ADDRESS, BALANCE, STOP];
+        let mut ran = ExecutionContext::new([1u8; 20], code.clone(), vec![]);
+        run(&mut ran).unwrap();
+        let mut stepped = ExecutionContext::new([1u8; 20], code, vec![]);
+        while !stepped.step().unwrap().halted {}
+        assert_eq!(ran.gas().used(), 102);
+        assert_eq!(stepped.gas().used(), 102);
+    }
+
+    #[test]
+    fn failed_step_reverts_storage() {
+        let mut ctx = ExecutionContext::new([0u8; 20], vec![PUSH1, 1, PUSH1, 0, SSTORE, INVALID], vec![]);
+        for _ in 0..3 {
+            ctx.step().unwrap();
+        }
+        assert_eq!(ctx.storage().load(U256::zero()).unwrap(), U256::one());
+        assert!(ctx.step().is_err());
+        assert!(!ctx.storage().contains(U256::zero()));
+        
+        // Undoing the failure brings the write back
+        assert!(ctx.step_back());
+        assert_eq!(ctx.storage().load(U256::zero()).unwrap(), U256::one());
+    }
+
+    #[test]
+    fn from_hex_decodes_code_and_calldata() {
+        let mut ctx = ExecutionContext::from_hex([0u8; 20], "6002600301", "abcd").unwrap();
+        run(&mut ctx).unwrap();
+        assert_eq!(ctx.stack().peek(0).unwrap(), U256::from(5));
+        assert_eq!(ctx.calldata().size(), 2);
+
+        let ctx = ExecutionContext::from_hex([0u8; 20], "0x6002", "0xabcd").unwrap();
+        assert_eq!(ctx.code(), &[PUSH1, 2]);
+        assert_eq!(ctx.calldata().size(), 2);
+
+        assert!(ExecutionContext::from_hex([0u8; 20], "600", "").is_err());
+        assert!(ExecutionContext::from_hex([0u8; 20], "zz", "").is_err());
+    }
+
+    #[test]
+    fn builder_sets_every_field() {
+        let mut state = WorldState::new();
+        state.set_balance([5; 20], U256::from(77));
+        let block = BlockEnv { number: U256::from(12), ..BlockEnv::default() };
+        let ctx = ExecutionContext::builder()
+            .address([5; 20])
+            .code(vec![STOP])
+            .calldata(vec![1, 2])
+            .caller([6; 20])
+            .origin([8; 20])
+            .value(U256::from(3))
+            .gas_price(U256::from(4))
+            .gas_limit(1000)
+            .step_limit(50)
+            .max_stack_depth(16)
+            .block(block)
+            .state(state)
+            .hardfork(Hardfork::Berlin)
+            .build();
+        assert_eq!(ctx.contract_address(), &[5; 20]);
+        assert_eq!(ctx.code(), &[STOP]);
+        assert_eq!(ctx.calldata().size(), 2);
+        assert_eq!(ctx.tx_env().caller, [6; 20]);
+        assert_eq!(ctx.tx_env().origin, [8; 20]);
+        assert_eq!(ctx.tx_env().value, U256::from(3));
+        assert_eq!(ctx.tx_env().gas_price, U256::from(4));
+        assert_eq!(ctx.gas().limit(), 1000);
+        assert_eq!(ctx.step_limit(), Some(50));
+        assert_eq!(ctx.stack().max_depth(), 16);
+        assert_eq!(ctx.block_env().number, U256::from(12));
+        assert_eq!(ctx.state().balance(&[5; 20]), U256::from(77));
+        assert_eq!(ctx.hardfork(), Hardfork::Berlin);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn execution_state_survives_json() {
+        let code = vec![PUSH1, 7, PUSH1, 1, SSTORE, PUSH1, 0xaa, PUSH1, 0, MSTORE, PUSH1, 3];
+        let mut ctx = ExecutionContext::new([0u8; 20], code, vec![]);
+        for _ in 0..5 {
+            ctx.step().unwrap();
+        }
+        let state = ctx.execution_state();
+        let json = serde_json::to_string(&state).unwrap();
+        assert!(json.contains("\"pc\":9"));
+        let back: ExecutionState = serde_json::from_str(&json).unwrap();
+        assert_eq!(back, state);
+    }
+
+    #[test]
+    fn return_range_must_be_in_memory() {
+        // RETURN expands memory first, so the range reads as zeros
+        let mut ctx = ExecutionContext::new([0u8; 20], vec![PUSH1, 40, PUSH1, 10, RETURN], vec![]);
+        run(&mut ctx).unwrap();
+        assert_eq!(ctx.return_data(), &vec![0u8; 40]);
+        assert_eq!(ctx.memory().size(), 64);
+
+        // Called directly, an unexpanded range is an error
+        let mut ctx = ExecutionContext::new([0u8; 20], vec![], vec![]);
+        assert!(matches!(ctx.set_return_data(0, 32), Err(MemoryError::MemoryAccessError)));
+        assert!(matches!(ctx.set_return_data(usize::MAX, 2), Err(MemoryError::MemoryOverflow)));
+        assert!(!ctx.stopped());
+        ctx.set_return_data(usize::MAX, 0).unwrap();
+        assert!(ctx.return_data().is_empty());
+    }
+
+    #[test]
+    fn return_data_decodes_as_a_word_or_address() {
+        let mut ctx = ExecutionContext::new([0u8; 20], vec![PUSH2, 0x12, 0x34, PUSH0, MSTORE, PUSH1, 32, PUSH0, RETURN], vec![]);
+        run(&mut ctx).unwrap();
+        assert_eq!(ctx.return_data_as_u256(), Some(U256::from(0x1234)));
+        let mut address = [0u8; 20];
+        address[18..].copy_from_slice(&[0x12, 0x34]);
+        assert_eq!(ctx.return_data_as_address(), Some(address));
+        assert_eq!(ctx.return_data_hex(), format!("0x{}1234", "0".repeat(60)));
+
+        // Anything but a single word does not decode
+        let mut ctx = ExecutionContext::new([0u8; 20], vec![PUSH1, 0xff, PUSH0, MSTORE8, PUSH1, 31, PUSH0, RETURN], vec![]);
+        run(&mut ctx).unwrap();
+        assert_eq!(ctx.return_data_as_u256(), None);
+        assert_eq!(ctx.return_data_as_address(), None);
+
+        // A word with its high bytes set is not an address
+        let mut ctx = ExecutionContext::new([0u8; 20], vec![PUSH1, 0xff, PUSH0, MSTORE8, PUSH1, 32, PUSH0, RETURN], vec![]);
+        run(&mut ctx).unwrap();
+        assert!(ctx.return_data_as_u256().is_some());
+        assert_eq!(ctx.return_data_as_address(), None);
+    }
+
+    #[test]
+    fn dump_shows_the_whole_frame() {
+        let code = vec![PUSH1, 7, PUSH1, 2, SSTORE, PUSH1, 9, PUSH1, 1, SSTORE, PUSH1, 0xff, PUSH0, MSTORE8, PUSH1, 1, PUSH1, 0xab];
+        let mut ctx = ExecutionContext::new([0u8; 20], code, vec![]);
+        run(&mut ctx).unwrap();
+        let expected = format!(
+            "pc:      18\nstatus:  Stopped\nstack:   [0xab, 0x1]\nmemory:\n0000: ff{}\nstorage:\n0x1: 0x9\n0x2: 0x7",
+            "00".repeat(31)
+        );
+        assert_eq!(ctx.dump(), expected);
+
+        let empty = ExecutionContext::new([0u8; 20], vec![], vec![]).dump();
+        assert!(empty.ends_with("stack:   []\nmemory:\n<empty>\nstorage:\n<empty>"));
+    }
+
+    #[test]
+    fn stepping_back_retraces_forward_steps() {
+        let code = vec![PUSH1, 7, PUSH1, 1, SSTORE, PUSH1, 0xaa, PUSH1, 0x40, MSTORE, STOP];
+        let mut ctx = ExecutionContext::new([0u8; 20], code, vec![]);
+        ctx.step().unwrap();
+        let after_one = ctx.execution_state();
+        let refund_after_one = ctx.gas_refund();
+        ctx.step().unwrap();
+        ctx.step().unwrap();
+        assert_eq!(ctx.storage().load(U256::one()).unwrap(), U256::from(7));
+
+        assert!(ctx.step_back());
+        assert!(ctx.step_back());
+        assert_eq!(ctx.execution_state(), after_one);
+        assert_eq!(ctx.gas_refund(), refund_after_one);
+        // The SSTORE's write and warm slot are undone with it
+        assert!(!ctx.storage().contains(U256::one()));
+        assert!(!ctx.access_list().is_warm_slot(&[0u8; 20], U256::one()));
+
+        assert!(ctx.step_back());
+        assert!(!ctx.step_back());
+    }
 }
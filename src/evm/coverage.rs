@@ -0,0 +1,92 @@
+use crate::evm::collections::Set;
+use crate::evm::context::ExecutionContext;
+use crate::evm::instruction::instructions;
+use crate::evm::interpreter::Outcome;
+use crate::evm::tracer::Tracer;
+use alloc::vec::Vec;
+
+// Records the PC of every instruction the traced frame executes
+#[derive(Default)]
+pub struct CoverageTracer {
+    pcs: Set<usize>,
+}
+
+impl CoverageTracer {
+
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn into_pcs(self) -> Set<usize> {
+        self.pcs
+    }
+}
+
+impl Tracer for CoverageTracer {
+    fn before_step(&mut self, ctx: &ExecutionContext) {
+        self.pcs.insert(ctx.pc());
+    }
+}
+
+// Which instructions of a program were executed, in PC order
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CoverageReport {
+    // (pc, opcode) of each instruction at least one run executed
+    pub covered: Vec<(usize, u8)>,
+    // (pc, opcode) of each instruction no run reached
+    pub uncovered: Vec<(usize, u8)>,
+}
+
+impl CoverageReport {
+
+    pub fn is_complete(&self) -> bool {
+        self.uncovered.is_empty()
+    }
+}
+
+// Combine the runs of one program into a report of which of its
+// instructions ran. PUSH immediates are data, not instructions, so they are
+// never listed. Runs that failed produce no Outcome and so count for nothing
+pub fn coverage(code: &[u8], runs: &[Outcome]) -> CoverageReport {
+    let (covered, uncovered) = instructions(code)
+        .map(|instruction| (instruction.pc, instruction.opcode))
+        .partition(|(pc, _)| runs.iter().any(|run| run.executed_pcs.contains(pc)));
+    CoverageReport { covered, uncovered }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::evm::interpreter::try_run;
+    use crate::evm::opcodes::*;
+    use alloc::vec;
+
+    // Store 2 in slot 0 if the first calldata word is nonzero, else store 1
+    const BRANCH: [u8; 16] = [
+        PUSH0, CALLDATALOAD, PUSH1, 10, JUMPI,
+        PUSH1, 1, PUSH0, SSTORE, STOP,
+        JUMPDEST, PUSH1, 2, PUSH0, SSTORE, STOP,
+    ];
+
+    fn pcs(instructions: &[(usize, u8)]) -> Vec<usize> {
+        instructions.iter().map(|(pc, _)| *pc).collect()
+    }
+
+    #[test]
+    fn untaken_branch_is_uncovered() {
+        let mut calldata = vec![0u8; 32];
+        calldata[0] = 1;
+        let taken = try_run(&BRANCH, &calldata).unwrap();
+        let report = coverage(&BRANCH, core::slice::from_ref(&taken));
+        assert_eq!(pcs(&report.covered), [0, 1, 2, 4, 10, 11, 13, 14, 15]);
+        assert_eq!(pcs(&report.uncovered), [5, 7, 8, 9]);
+        assert_eq!(report.uncovered[0], (5, PUSH1));
+        assert!(!report.is_complete());
+
+        // Falling through as well covers everything
+        let fallthrough = try_run(&BRANCH, &[]).unwrap();
+        let report = coverage(&BRANCH, &[taken, fallthrough]);
+        assert!(report.is_complete());
+        assert_eq!(report.covered.len(), 13);
+    }
+}
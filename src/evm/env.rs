@@ -0,0 +1,43 @@
+use crate::evm::context::Address;
+use primitive_types::U256;
+use crate::evm::collections::Map;
+use crate::evm::constants::DEFAULT_CHAIN_ID;
+
+// Transaction-level values visible to the executing code
+#[derive(Clone, Default)]
+pub struct TxEnv {
+    pub caller: Address,
+    pub origin: Address,
+    pub value: U256,
+    pub gas_price: U256,
+}
+
+// Block-level values for the block the transaction is included in
+#[derive(Clone)]
+pub struct BlockEnv {
+    pub coinbase: Address,
+    pub timestamp: U256,
+    pub number: U256,
+    pub gas_limit: U256,
+    // Replaces DIFFICULTY since the merge
+    pub prev_randao: U256,
+    pub base_fee: U256,
+    pub chain_id: U256,
+    // Hashes of previous blocks by number, served by BLOCKHASH
+    pub block_hashes: Map<u64, U256>,
+}
+
+impl Default for BlockEnv {
+    fn default() -> Self {
+        BlockEnv {
+            coinbase: Address::default(),
+            timestamp: U256::zero(),
+            number: U256::zero(),
+            gas_limit: U256::zero(),
+            prev_randao: U256::zero(),
+            base_fee: U256::zero(),
+            chain_id: U256::from(DEFAULT_CHAIN_ID),
+            block_hashes: Map::new(),
+        }
+    }
+}
@@ -0,0 +1,260 @@
+use crate::evm::hardfork::Hardfork;
+use crate::evm::opcodes::*;
+use primitive_types::U256;
+
+// EIP-2929 access costs. The warm cost is part of each opcode's static cost;
+// a cold access pays the difference on top
+pub const WARM_STORAGE_READ_COST: u64 = 100;
+pub const COLD_ACCOUNT_ACCESS_COST: u64 = 2600;
+pub const COLD_SLOAD_COST: u64 = 2100;
+
+// SSTORE prices. Berlin moved the cold read out of the reset cost into
+// COLD_SLOAD_COST, and London (EIP-3529) cut the refund for clearing a slot
+pub const SSTORE_SET_COST: u64 = 20000;
+pub const SSTORE_RESET_COST: u64 = 5000;
+pub const SSTORE_CLEAR_REFUND: i64 = 15000;
+pub const SSTORE_CLEAR_REFUND_LONDON: i64 = 4800;
+// SSTORE fails outright unless more than this much gas is left
+pub const SSTORE_SENTRY: u64 = 2300;
+
+// A CALL that sends value pays extra, and its callee gets a stipend on top
+// of the gas it is given
+pub const CALL_VALUE_COST: u64 = 9000;
+pub const CALL_STIPEND: u64 = 2300;
+
+// Tracks gas consumption against the limit for one execution
+pub struct Gas {
+    limit: u64,
+    used: u64,
+}
+
+impl Gas {
+
+    pub fn new(limit: u64) -> Self {
+        Gas {
+            limit,
+            used: 0,
+        }
+    }
+
+    pub fn limit(&self) -> u64 {
+        self.limit
+    }
+
+    pub fn used(&self) -> u64 {
+        self.used
+    }
+
+    pub fn remaining(&self) -> u64 {
+        self.limit - self.used
+    }
+
+    // Consume gas, failing without charging anything if it would exceed the limit
+    pub fn charge(&mut self, cost: u64) -> Result<(), InstructionError> {
+        if cost > self.remaining() {
+            return Err(InstructionError::OutOfGas);
+        }
+        self.used += cost;
+        Ok(())
+    }
+
+    // Overwrite the gas used so far, never past the limit
+    pub fn set_used(&mut self, used: u64) {
+        self.used = used.min(self.limit);
+    }
+
+    // Exceptional halts forfeit whatever gas is left
+    pub fn consume_all(&mut self) {
+        self.used = self.limit;
+    }
+}
+
+// Static base cost of each opcode under a fork. Dynamic parts such as
+// memory expansion and copy costs are charged separately by the handlers,
+// and SSTORE is priced entirely by its handler. Undefined opcodes cost
+// nothing; they fail before any gas matters
+pub const fn static_gas_cost(opcode: u8, fork: Hardfork) -> u64 {
+    use Hardfork::*;
+    match (opcode, fork) {
+        // State access was repriced by EIP-150 (folded into Byzantium here)
+        // and EIP-1884 in Istanbul, then split into warm and cold in Berlin
+        (SLOAD, Frontier | Homestead) => 50,
+        (SLOAD, Byzantium | Constantinople) => 200,
+        (SLOAD, Istanbul) => 800,
+        (BALANCE | EXTCODESIZE | EXTCODECOPY, Frontier | Homestead) => 20,
+        (BALANCE, Byzantium | Constantinople) => 400,
+        (BALANCE | EXTCODESIZE | EXTCODECOPY, Byzantium | Constantinople | Istanbul) => 700,
+        (EXTCODEHASH, Constantinople) => 400,
+        (EXTCODEHASH, Istanbul) => 700,
+        (CALL | DELEGATECALL | STATICCALL, Frontier | Homestead) => 40,
+        (CALL | DELEGATECALL | STATICCALL, Byzantium | Constantinople | Istanbul) => 700,
+        (SELFDESTRUCT, Frontier | Homestead) => 0,
+        _ => latest_static_gas_cost(opcode),
+    }
+}
+
+// Static costs under the latest fork, which earlier forks differ from only
+// for the state access opcodes
+const fn latest_static_gas_cost(opcode: u8) -> u64 {
+    match opcode {
+        STOP | RETURN | REVERT | SSTORE => 0,
+        JUMPDEST => 1,
+        ADDRESS | ORIGIN | CALLER | CALLVALUE | CALLDATASIZE | CODESIZE | GASPRICE
+        | COINBASE | TIMESTAMP | NUMBER | PREVRANDAO | GASLIMIT | CHAINID | BASEFEE
+        | POP | PC | MSIZE | GAS | PUSH0 => 2,
+        ADD | SUB | LT | GT | SLT | SGT | EQ | ISZERO | AND | OR | XOR | NOT | BYTE
+        | SHL | SHR | SAR | CALLDATALOAD | CALLDATACOPY | CODECOPY
+        | MLOAD | MSTORE | MSTORE8 | MCOPY => 3,
+        PUSH1..=PUSH32 | DUP1..=DUP16 | SWAP1..=SWAP16 => 3,
+        MUL | DIV | SDIV | MOD | SMOD | SELFBALANCE => 5,
+        ADDMOD | MULMOD | JUMP => 8,
+        EXP | JUMPI => 10,
+        BLOCKHASH => 20,
+        SHA3 => 30,
+        // Warm access costs; cold surcharges are dynamic
+        BALANCE | EXTCODESIZE | EXTCODECOPY | EXTCODEHASH | SLOAD => 100,
+        TLOAD | TSTORE => 100,
+        LOG0..=LOG4 => 375 * (1 + (opcode - LOG0) as u64),
+        CALL | DELEGATECALL | STATICCALL => 100,
+        SELFDESTRUCT => 5000,
+        CREATE => 32000,
+        _ => 0,
+    }
+}
+
+// static_gas_cost for every byte value under the latest fork, for tools
+// that want a lookup table
+pub const GAS_COSTS: [u64; 256] = {
+    let mut table = [0u64; 256];
+    let mut opcode = 0;
+    while opcode < 256 {
+        table[opcode] = static_gas_cost(opcode as u8, Hardfork::LATEST);
+        opcode += 1;
+    }
+    table
+};
+
+// Dynamic part of EXP's cost: 10 per byte of the exponent, raised to 50 by
+// EIP-160 (folded into Byzantium here)
+pub fn exp_cost(exponent: U256, fork: Hardfork) -> u64 {
+    let per_byte = if fork >= Hardfork::Byzantium { 50 } else { 10 };
+    per_byte * exponent.bits().div_ceil(8) as u64
+}
+
+// Gas cost and refund change of an SSTORE, excluding any cold surcharge.
+// From Istanbul on this is net gas metering (EIP-2200): the price depends on
+// the slot's value at the start of the transaction (original), its value now
+// (current) and the value being written (new), and only the first write to
+// a clean slot pays full price
+pub fn sstore_cost(original: U256, current: U256, new: U256, fork: Hardfork) -> (u64, i64) {
+    if fork < Hardfork::Istanbul {
+        // Before that only the current value mattered
+        let cost = if current.is_zero() && !new.is_zero() { SSTORE_SET_COST } else { SSTORE_RESET_COST };
+        let refund = if !current.is_zero() && new.is_zero() { SSTORE_CLEAR_REFUND } else { 0 };
+        return (cost, refund);
+    }
+    // A write that changes nothing, or a slot already dirty, costs one read
+    let (read_cost, reset_cost) = if fork.has_access_lists() {
+        (WARM_STORAGE_READ_COST, SSTORE_RESET_COST - COLD_SLOAD_COST)
+    } else {
+        (static_gas_cost(SLOAD, fork), SSTORE_RESET_COST)
+    };
+    let clear_refund = if fork >= Hardfork::London { SSTORE_CLEAR_REFUND_LONDON } else { SSTORE_CLEAR_REFUND };
+    
+    if new == current {
+        return (read_cost, 0);
+    }
+    if original == current {
+        // First write to a clean slot
+        let cost = if original.is_zero() { SSTORE_SET_COST } else { reset_cost };
+        let refund = if new.is_zero() { clear_refund } else { 0 };
+        return (cost, refund);
+    }
+    
+    // The slot is already dirty, so the write itself is cheap; adjust the
+    // refunds granted or taken back by earlier writes
+    let mut refund = 0;
+    if !original.is_zero() {
+        if current.is_zero() {
+            refund -= clear_refund;
+        }
+        if new.is_zero() {
+            refund += clear_refund;
+        }
+    }
+    if new == original {
+        let full_cost = if original.is_zero() { SSTORE_SET_COST } else { reset_cost };
+        refund += (full_cost - read_cost) as i64;
+    }
+    (read_cost, refund)
+}
+
+// Per-word cost of copying `length` bytes into memory (the *COPY opcodes)
+pub fn copy_cost(length: usize) -> u64 {
+    (length.div_ceil(32) as u64).saturating_mul(3)
+}
+
+// Per-word cost of hashing `length` bytes with SHA3, on top of its static 30
+pub fn sha3_cost(length: usize) -> u64 {
+    (length.div_ceil(32) as u64).saturating_mul(6)
+}
+
+// Total cost of holding `words` 32-byte words of memory: 3 per word plus a
+// quadratic term that makes very large allocations prohibitively expensive
+pub fn memory_cost(words: u64) -> u64 {
+    // Squared in 128 bits so huge sizes saturate the total rather than
+    // just the square, which would price them far too low
+    let words = words as u128;
+    let cost = words * 3 + words * words / 512;
+    u64::try_from(cost).unwrap_or(u64::MAX)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn static_costs_follow_the_fork() {
+        assert_eq!(static_gas_cost(SLOAD, Hardfork::Frontier), 50);
+        assert_eq!(static_gas_cost(SLOAD, Hardfork::Istanbul), 800);
+        assert_eq!(static_gas_cost(SLOAD, Hardfork::Berlin), WARM_STORAGE_READ_COST);
+        assert_eq!(static_gas_cost(BALANCE, Hardfork::Byzantium), 400);
+        assert_eq!(static_gas_cost(BALANCE, Hardfork::Istanbul), 700);
+        assert_eq!(static_gas_cost(CALL, Hardfork::Homestead), 40);
+        assert_eq!(static_gas_cost(ADD, Hardfork::Frontier), 3);
+    }
+
+    #[test]
+    fn exp_byte_cost_rises_in_byzantium() {
+        assert_eq!(exp_cost(U256::from(0x100), Hardfork::Homestead), 20);
+        assert_eq!(exp_cost(U256::from(0x100), Hardfork::Byzantium), 100);
+    }
+
+    #[test]
+    fn sstore_metering_per_fork() {
+        let (zero, one, two) = (U256::zero(), U256::one(), U256::from(2));
+        // Before Istanbul only the current value matters
+        assert_eq!(sstore_cost(zero, zero, one, Hardfork::Byzantium), (20000, 0));
+        assert_eq!(sstore_cost(one, two, zero, Hardfork::Byzantium), (5000, 15000));
+        // A no-op costs a read
+        assert_eq!(sstore_cost(one, one, one, Hardfork::Istanbul), (800, 0));
+        assert_eq!(sstore_cost(one, one, one, Hardfork::London), (100, 0));
+        // Clearing a clean slot, before and after EIP-3529
+        assert_eq!(sstore_cost(one, one, zero, Hardfork::Istanbul), (5000, 15000));
+        assert_eq!(sstore_cost(one, one, zero, Hardfork::London), (2900, 4800));
+        // Restoring a dirty slot refunds all but the read
+        assert_eq!(sstore_cost(zero, one, zero, Hardfork::Istanbul), (800, 19200));
+    }
+
+    #[test]
+    fn table_holds_the_latest_costs() {
+        assert_eq!(GAS_COSTS[ADD as usize], 3);
+        assert_eq!(GAS_COSTS[MUL as usize], 5);
+        // Warm under EIP-2929; the cold surcharge is charged separately
+        assert_eq!(GAS_COSTS[SLOAD as usize], WARM_STORAGE_READ_COST);
+        assert_eq!(GAS_COSTS[JUMPDEST as usize], 1);
+        for opcode in 0..=255u8 {
+            assert_eq!(GAS_COSTS[opcode as usize], static_gas_cost(opcode, Hardfork::LATEST));
+        }
+    }
+}
@@ -0,0 +1,97 @@
+use crate::evm::opcodes::{
+    BASEFEE, CHAINID, DELEGATECALL, EXTCODEHASH, MCOPY, PUSH0, REVERT, SAR, SELFBALANCE, SHL, SHR, STATICCALL, TLOAD, TSTORE,
+};
+
+// Protocol upgrades that changed which opcodes exist or how they are priced,
+// in activation order. Each fork switches opcode availability, static gas
+// costs, SSTORE metering and EXP's per-byte cost, and from Berlin on the
+// cold access surcharges. Forks that only repriced are folded into the next
+// one listed: Tangerine Whistle and Spurious Dragon into Byzantium, and
+// Constantinople stands for Petersburg, without EIP-1283
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Hardfork {
+    Frontier,
+    // DELEGATECALL
+    Homestead,
+    // REVERT and STATICCALL
+    Byzantium,
+    // SHL, SHR, SAR and EXTCODEHASH
+    Constantinople,
+    // CHAINID and SELFBALANCE
+    Istanbul,
+    // Cold and warm access costs (EIP-2929)
+    Berlin,
+    // BASEFEE
+    London,
+    // PUSH0
+    Shanghai,
+    // TLOAD, TSTORE and MCOPY
+    #[default]
+    Cancun,
+}
+
+impl Hardfork {
+
+    pub const LATEST: Hardfork = Hardfork::Cancun;
+
+    // The fork an opcode first became available in; opcodes that do not
+    // exist at all report Frontier and are rejected by the dispatcher
+    pub const fn introduced_in(opcode: u8) -> Hardfork {
+        match opcode {
+            DELEGATECALL => Hardfork::Homestead,
+            REVERT | STATICCALL => Hardfork::Byzantium,
+            SHL | SHR | SAR | EXTCODEHASH => Hardfork::Constantinople,
+            CHAINID | SELFBALANCE => Hardfork::Istanbul,
+            BASEFEE => Hardfork::London,
+            PUSH0 => Hardfork::Shanghai,
+            TLOAD | TSTORE | MCOPY => Hardfork::Cancun,
+            _ => Hardfork::Frontier,
+        }
+    }
+
+    pub fn is_active(self, opcode: u8) -> bool {
+        self >= Hardfork::introduced_in(opcode)
+    }
+
+    // Whether first accesses to accounts and slots pay the cold surcharge
+    pub fn has_access_lists(self) -> bool {
+        self >= Hardfork::Berlin
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::evm::context::ExecutionContext;
+    use crate::evm::interpreter::run;
+    use crate::evm::opcodes::{InstructionError, PUSH1, SLOAD, SSTORE};
+    use alloc::vec;
+
+    fn gas_used(fork: Hardfork, code: alloc::vec::Vec<u8>) -> u64 {
+        let mut ctx = ExecutionContext::builder().code(code).hardfork(fork).build();
+        run(&mut ctx).unwrap();
+        ctx.gas().used()
+    }
+
+    #[test]
+    fn push0_needs_shanghai() {
+        let mut ctx = ExecutionContext::builder().code(vec![PUSH0]).hardfork(Hardfork::London).build();
+        let err = run(&mut ctx).unwrap_err();
+        assert!(matches!(err.kind, InstructionError::InvalidOpcode));
+        
+        let mut ctx = ExecutionContext::builder().code(vec![PUSH0]).hardfork(Hardfork::Shanghai).build();
+        run(&mut ctx).unwrap();
+        assert_eq!(ctx.gas().used(), 2);
+        assert_eq!(ctx.stack().len(), 1);
+    }
+
+    #[test]
+    fn storage_is_priced_per_fork() {
+        // SSTORE(0 -> 0) then SLOAD of the same slot
+        let code = vec![PUSH1, 0, PUSH1, 0, SSTORE, PUSH1, 0, SLOAD];
+        assert_eq!(gas_used(Hardfork::Homestead, code.clone()), 9 + 5000 + 50);
+        assert_eq!(gas_used(Hardfork::Byzantium, code.clone()), 9 + 5000 + 200);
+        assert_eq!(gas_used(Hardfork::Istanbul, code.clone()), 9 + 800 + 800);
+        assert_eq!(gas_used(Hardfork::Berlin, code), 9 + 2100 + 100 + 100);
+    }
+}
@@ -0,0 +1,10 @@
+use tiny_keccak::{Hasher, Keccak};
+
+// Keccak-256 as used by the EVM (the pre-standard SHA3 padding)
+pub fn keccak256(data: &[u8]) -> [u8; 32] {
+    let mut hasher = Keccak::v256();
+    let mut output = [0u8; 32];
+    hasher.update(data);
+    hasher.finalize(&mut output);
+    output
+}
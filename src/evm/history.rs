@@ -0,0 +1,260 @@
+use crate::evm::access_list::AccessList;
+use crate::evm::constants::STEP_HISTORY_LIMIT;
+use crate::evm::context::{Address, ExecStatus, ExecutionContext};
+use crate::evm::instruction::opcode_info;
+use crate::evm::interpreter::FrameSnapshot;
+use crate::evm::opcodes::*;
+use crate::evm::state::WorldState;
+use crate::evm::storage::{Storage, TransientStorage};
+use primitive_types::U256;
+use crate::evm::collections::{Map, Set};
+use alloc::{boxed::Box, collections::VecDeque, vec::Vec};
+
+// The most recent steps taken with ExecutionContext::step, oldest first, so
+// a debugger can walk back through them. Once full the oldest step is
+// forgotten
+#[derive(Default)]
+pub(crate) struct StepHistory {
+    steps: VecDeque<StepSnapshot>,
+}
+
+impl StepHistory {
+
+    pub(crate) fn push(&mut self, snapshot: StepSnapshot) {
+        if self.steps.len() == STEP_HISTORY_LIMIT {
+            self.steps.pop_front();
+        }
+        self.steps.push_back(snapshot);
+    }
+
+    pub(crate) fn pop(&mut self) -> Option<StepSnapshot> {
+        self.steps.pop_back()
+    }
+
+    pub(crate) fn clear(&mut self) {
+        self.steps.clear();
+    }
+}
+
+// What one instruction is about to overwrite, taken just before it runs.
+// Rather than copying the frame, only the stack items it consumes, the
+// memory bytes and storage slots it writes and the counters it moves are
+// kept; the rest of the frame is the same after undoing it
+pub(crate) struct StepSnapshot {
+    pc: usize,
+    // Stack height before the step, and the items it pops, top first
+    stack_len: usize,
+    stack_top: Vec<U256>,
+    gas_used: u64,
+    gas_refund: i64,
+    memory_size: usize,
+    // The bytes a memory write lands on, as (offset, old bytes). Only the
+    // part inside the current memory is kept, since anything beyond it is
+    // cut off again when memory shrinks back to memory_size
+    memory: Option<(usize, Vec<u8>)>,
+    storage: Option<SlotDelta>,
+    transient: Option<SlotDelta>,
+    // A slot of this contract or an account that was still cold
+    cold_slot: Option<U256>,
+    cold_address: Option<Address>,
+    logs_len: usize,
+    truncated_push: bool,
+    return_data: Option<Vec<u8>>,
+    // Instructions that run or destroy accounts can change any of them
+    world: Option<Box<WorldSnapshot>>,
+    // The frame this step finished, which undoing it starts up again
+    frame: Option<FrameSnapshot>,
+}
+
+// A slot's value before a write: None if it had never been written, which
+// restoring a zero would not reproduce
+struct SlotDelta {
+    slot: U256,
+    previous: Option<U256>,
+    // Whether the slot's start-of-transaction value was already recorded;
+    // if not, the write records it and undoing must forget it again
+    original_known: bool,
+}
+
+struct WorldSnapshot {
+    state: WorldState,
+    storage: Storage,
+    transient_storage: TransientStorage,
+    destroyed: Set<Address>,
+    access_list: AccessList,
+    original_storage: Map<(Address, U256), U256>,
+}
+
+impl StepSnapshot {
+
+    pub(crate) fn capture(ctx: &ExecutionContext, opcode: u8) -> Self {
+        let stack = ctx.stack();
+        // An instruction short of operands fails before changing anything
+        let popped = opcode_info(opcode).map_or(0, |info| info.stack_in as usize).min(stack.len());
+        let mut snapshot = StepSnapshot {
+            pc: ctx.pc(),
+            stack_len: stack.len(),
+            stack_top: stack.iter().take(popped).copied().collect(),
+            gas_used: ctx.gas().used(),
+            gas_refund: ctx.gas_refund(),
+            memory_size: ctx.memory().size(),
+            memory: None,
+            storage: None,
+            transient: None,
+            cold_slot: None,
+            cold_address: None,
+            logs_len: ctx.logs().len(),
+            truncated_push: ctx.truncated_push(),
+            return_data: None,
+            world: None,
+            frame: None,
+        };
+        
+        let arg = |n: usize| snapshot.stack_top.get(n).copied();
+        let address = *ctx.contract_address();
+        match opcode {
+            MSTORE => snapshot.memory = memory_region(ctx, arg(0), Some(U256::from(32))),
+            MSTORE8 => snapshot.memory = memory_region(ctx, arg(0), Some(U256::one())),
+            CALLDATACOPY | CODECOPY | MCOPY => snapshot.memory = memory_region(ctx, arg(0), arg(2)),
+            EXTCODECOPY => {
+                snapshot.memory = memory_region(ctx, arg(1), arg(3));
+                snapshot.cold_address = arg(0).map(word_to_address).filter(|a| !ctx.access_list().is_warm_address(a));
+            }
+            BALANCE | EXTCODESIZE | EXTCODEHASH => {
+                snapshot.cold_address = arg(0).map(word_to_address).filter(|a| !ctx.access_list().is_warm_address(a));
+            }
+            SLOAD => snapshot.cold_slot = arg(0).filter(|&slot| !ctx.access_list().is_warm_slot(&address, slot)),
+            SSTORE => {
+                snapshot.cold_slot = arg(0).filter(|&slot| !ctx.access_list().is_warm_slot(&address, slot));
+                snapshot.storage = arg(0).map(|slot| SlotDelta {
+                    slot,
+                    previous: ctx.storage().contains(slot).then(|| ctx.storage().load(slot).unwrap_or_default()),
+                    original_known: ctx.original_storage().contains_key(&(address, slot)),
+                });
+            }
+            TSTORE => {
+                snapshot.transient = arg(0).map(|slot| SlotDelta {
+                    slot,
+                    previous: ctx.transient_storage().contains(&address, slot).then(|| ctx.transient_storage().load(&address, slot).unwrap_or_default()),
+                    original_known: true,
+                });
+            }
+            RETURN | REVERT => snapshot.return_data = Some(ctx.return_data().clone()),
+            CALL | DELEGATECALL | STATICCALL | CREATE | SELFDESTRUCT => {
+                // The output area of a call, or nothing for CREATE and SELFDESTRUCT
+                snapshot.memory = match opcode {
+                    CALL => memory_region(ctx, arg(5), arg(6)),
+                    DELEGATECALL | STATICCALL => memory_region(ctx, arg(4), arg(5)),
+                    _ => None,
+                };
+                snapshot.return_data = Some(ctx.return_data().clone());
+                snapshot.world = Some(Box::new(WorldSnapshot::capture(ctx)));
+            }
+            _ => {}
+        }
+        snapshot
+    }
+
+    // Called when the step halted or failed, before the frame is ended.
+    // Ending it can revert storage or delete accounts, which the per-step
+    // deltas cannot see, so the world as the step left it is kept instead.
+    // An instruction that already saved the world did so before it ran,
+    // which is what undoing needs anyway
+    pub(crate) fn finish_frame(&mut self, ctx: &ExecutionContext, frame: FrameSnapshot) {
+        if self.world.is_none() {
+            self.world = Some(Box::new(WorldSnapshot::capture(ctx)));
+        }
+        self.frame = Some(frame);
+    }
+
+    // Put the frame back the way it was before the instruction ran. The
+    // world goes back first, since it may have been saved after the step
+    pub(crate) fn undo(self, ctx: &mut ExecutionContext) {
+        if let Some(world) = self.world {
+            let world = *world;
+            *ctx.state_mut() = world.state;
+            *ctx.storage_mut() = world.storage;
+            *ctx.transient_storage_mut() = world.transient_storage;
+            *ctx.destroyed_mut() = world.destroyed;
+            *ctx.access_list_mut() = world.access_list;
+            *ctx.original_storage_mut() = world.original_storage;
+        }
+        if self.frame.is_some() {
+            ctx.set_frame(self.frame);
+        }
+        ctx.set_pc(self.pc);
+        ctx.set_status(ExecStatus::Running);
+        
+        let stack = ctx.stack_mut();
+        stack.truncate(self.stack_len - self.stack_top.len());
+        for value in self.stack_top.into_iter().rev() {
+            // The stack held these items a moment ago, so there is room
+            let _ = stack.push(value);
+        }
+        
+        ctx.gas_mut().set_used(self.gas_used);
+        ctx.add_gas_refund(self.gas_refund - ctx.gas_refund());
+        ctx.memory_mut().truncate(self.memory_size);
+        if let Some((offset, bytes)) = self.memory {
+            // The bytes came from inside memory, so they fit back
+            let _ = ctx.memory_mut().store_range(offset, &bytes);
+        }
+        ctx.logs_mut().truncate(self.logs_len);
+        ctx.set_truncated_push(self.truncated_push);
+        if let Some(return_data) = self.return_data {
+            *ctx.return_data_mut() = return_data;
+        }
+        
+        let address = *ctx.contract_address();
+        if let Some(delta) = self.storage {
+            if !delta.original_known {
+                ctx.original_storage_mut().remove(&(address, delta.slot));
+            }
+            restore_slot(ctx.storage_mut(), delta);
+        }
+        if let Some(delta) = self.transient {
+            restore_slot(ctx.transient_storage_mut().account_mut(address), delta);
+        }
+        if let Some(slot) = self.cold_slot {
+            ctx.access_list_mut().cool_slot(address, slot);
+        }
+        if let Some(account) = self.cold_address {
+            ctx.access_list_mut().cool_address(&account);
+        }
+    }
+}
+
+impl WorldSnapshot {
+
+    fn capture(ctx: &ExecutionContext) -> Self {
+        WorldSnapshot {
+            state: ctx.state().clone(),
+            storage: ctx.storage().clone(),
+            transient_storage: ctx.transient_storage().clone(),
+            destroyed: ctx.destroyed().clone(),
+            access_list: ctx.access_list().clone(),
+            original_storage: ctx.original_storage().clone(),
+        }
+    }
+}
+
+// The part of [offset, offset + length) inside memory as it is now, or None
+// if nothing there can be overwritten
+fn memory_region(ctx: &ExecutionContext, offset: Option<U256>, length: Option<U256>) -> Option<(usize, Vec<u8>)> {
+    let size = ctx.memory().size();
+    let (offset, length) = (to_usize(offset?).ok()?, to_usize(length?).ok()?);
+    if length == 0 || offset >= size {
+        return None;
+    }
+    Some((offset, ctx.memory().load_range(offset, length.min(size - offset)).ok()?))
+}
+
+fn restore_slot(storage: &mut Storage, delta: SlotDelta) {
+    match delta.previous {
+        // Writes to in-memory storage cannot fail
+        Some(value) => {
+            let _ = storage.store(delta.slot, value);
+        }
+        None => storage.remove(delta.slot),
+    }
+}
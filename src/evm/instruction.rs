@@ -0,0 +1,142 @@
+use crate::evm::disasm::mnemonic;
+use crate::evm::gas::static_gas_cost;
+use crate::evm::hardfork::Hardfork;
+use crate::evm::opcodes::*;
+use alloc::vec::Vec;
+
+// Static facts about an opcode, for tools that inspect bytecode without
+// running it
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct OpInfo {
+    pub mnemonic: &'static str,
+    // Items popped from the stack
+    pub stack_in: u8,
+    // Items pushed onto the stack
+    pub stack_out: u8,
+    // Bytes of code following the opcode that belong to it (PUSH data)
+    pub immediate_bytes: u8,
+    // Gas charged before any dynamic cost
+    pub static_gas: u64,
+}
+
+// One decoded instruction. PUSH1..PUSH32 carry their immediate, which is
+// shorter than the PUSH's width if the code ends partway through it
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Instruction {
+    pub pc: usize,
+    pub opcode: u8,
+    pub immediate: Option<Vec<u8>>,
+}
+
+// Walk the instructions of some code in order without executing them. PUSH
+// immediates are part of their instruction, so data bytes that happen to
+// look like opcodes are never yielded
+pub fn instructions(code: &[u8]) -> impl Iterator<Item = Instruction> + '_ {
+    let mut pc = 0;
+    core::iter::from_fn(move || {
+        let start = pc;
+        let opcode = *code.get(start)?;
+        pc += 1;
+        let immediate = (PUSH1..=PUSH32).contains(&opcode).then(|| {
+            let end = (pc + (opcode - PUSH1 + 1) as usize).min(code.len());
+            let bytes = code[pc..end].to_vec();
+            pc = end;
+            bytes
+        });
+        Some(Instruction { pc: start, opcode, immediate })
+    })
+}
+
+// Look up an opcode, or None if the byte is not a defined opcode
+pub fn opcode_info(opcode: u8) -> Option<OpInfo> {
+    let mnemonic = mnemonic(opcode)?;
+    let (stack_in, stack_out) = stack_io(opcode);
+    let immediate_bytes = if (PUSH1..=PUSH32).contains(&opcode) { opcode - PUSH1 + 1 } else { 0 };
+    Some(OpInfo {
+        mnemonic,
+        stack_in,
+        stack_out,
+        immediate_bytes,
+        static_gas: static_gas_cost(opcode, Hardfork::LATEST),
+    })
+}
+
+// (items popped, items pushed); DUPn and SWAPn count the items they read
+// as popped and put back
+fn stack_io(opcode: u8) -> (u8, u8) {
+    match opcode {
+        STOP | JUMPDEST | INVALID => (0, 0),
+
+        ADD | MUL | SUB | DIV | SDIV | MOD | SMOD | EXP => (2, 1),
+        ADDMOD | MULMOD => (3, 1),
+        LT | GT | SLT | SGT | EQ => (2, 1),
+        ISZERO | NOT => (1, 1),
+        AND | OR | XOR | BYTE | SHL | SHR | SAR => (2, 1),
+        SHA3 => (2, 1),
+
+        ADDRESS | ORIGIN | CALLER | CALLVALUE | CALLDATASIZE | CODESIZE | GASPRICE => (0, 1),
+        BALANCE | CALLDATALOAD | EXTCODESIZE | EXTCODEHASH => (1, 1),
+        CALLDATACOPY | CODECOPY | MCOPY => (3, 0),
+        EXTCODECOPY => (4, 0),
+
+        BLOCKHASH => (1, 1),
+        COINBASE | TIMESTAMP | NUMBER | PREVRANDAO | GASLIMIT | CHAINID | SELFBALANCE | BASEFEE => (0, 1),
+
+        POP | JUMP => (1, 0),
+        MLOAD | SLOAD | TLOAD => (1, 1),
+        MSTORE | MSTORE8 | SSTORE | TSTORE | JUMPI => (2, 0),
+        PC | MSIZE | GAS => (0, 1),
+
+        PUSH0..=PUSH32 => (0, 1),
+        DUP1..=DUP16 => {
+            let n = opcode - DUP1 + 1;
+            (n, n + 1)
+        }
+        SWAP1..=SWAP16 => {
+            let n = opcode - SWAP1 + 2;
+            (n, n)
+        }
+        LOG0..=LOG4 => (opcode - LOG0 + 2, 0),
+
+        CREATE => (3, 1),
+        CALL => (7, 1),
+        DELEGATECALL | STATICCALL => (6, 1),
+        RETURN | REVERT => (2, 0),
+        SELFDESTRUCT => (1, 0),
+
+        _ => (0, 0),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn info_for_common_opcodes() {
+        let add = opcode_info(ADD).unwrap();
+        assert_eq!(add.mnemonic, "ADD");
+        assert_eq!((add.stack_in, add.stack_out, add.immediate_bytes, add.static_gas), (2, 1, 0, 3));
+        let push3 = opcode_info(PUSH3).unwrap();
+        assert_eq!(push3.mnemonic, "PUSH3");
+        assert_eq!((push3.stack_in, push3.stack_out, push3.immediate_bytes), (0, 1, 3));
+        let dup1 = opcode_info(DUP1).unwrap();
+        assert_eq!((dup1.stack_in, dup1.stack_out, dup1.immediate_bytes), (1, 2, 0));
+        assert!(opcode_info(0x0c).is_none());
+    }
+
+    #[test]
+    fn pcs_skip_immediates_of_every_width() {
+        // The last PUSH4 is cut short by the end of the code
+        let code = [PUSH1, 0x5b, PUSH0, PUSH3, 0xaa, 0xbb, 0xcc, JUMPDEST, PUSH2, 1, 2, ADD, PUSH4, 0xdd, 0xee];
+        let decoded: Vec<Instruction> = instructions(&code).collect();
+        let pcs: Vec<usize> = decoded.iter().map(|instruction| instruction.pc).collect();
+        assert_eq!(pcs, [0, 2, 3, 7, 8, 11, 12]);
+        assert_eq!(decoded[0].immediate.as_deref(), Some(&[0x5b][..]));
+        assert_eq!(decoded[1].immediate, None);
+        assert_eq!(decoded[2].immediate.as_deref(), Some(&[0xaa, 0xbb, 0xcc][..]));
+        assert_eq!(decoded[3], Instruction { pc: 7, opcode: JUMPDEST, immediate: None });
+        assert_eq!(decoded[6].immediate.as_deref(), Some(&[0xdd, 0xee][..]));
+        assert_eq!(instructions(&[]).count(), 0);
+    }
+}
@@ -0,0 +1,295 @@
+use crate::evm::collections::Set;
+use crate::evm::constants::ESTIMATE_GAS_LIMIT;
+use crate::evm::context::ExecutionContext;
+use crate::evm::coverage::CoverageTracer;
+use crate::evm::opcodes::{execute_opcode, Control, InstructionError, STOP};
+use crate::evm::storage::{StorageSnapshot, TransientStorage};
+use crate::evm::tracer::Tracer;
+use core::fmt;
+use alloc::vec::Vec;
+
+// An instruction failure together with where in the program it happened
+#[derive(Debug)]
+pub struct ExecutionError {
+    pub kind: InstructionError,
+    pub pc: usize,
+    pub opcode: u8,
+}
+
+impl fmt::Display for ExecutionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} at pc={} opcode=0x{:02x}", self.kind, self.pc, self.opcode)
+    }
+}
+
+impl core::error::Error for ExecutionError {
+    fn source(&self) -> Option<&(dyn core::error::Error + 'static)> {
+        Some(&self.kind)
+    }
+}
+
+// How a try_run program finished when it did not fail outright
+#[derive(Debug)]
+pub struct Outcome {
+    // The program ended with REVERT rather than STOP or RETURN
+    pub reverted: bool,
+    pub return_data: Vec<u8>,
+    pub gas_used: u64,
+    // PCs of the instructions the program executed, for coverage
+    pub executed_pcs: Set<usize>,
+}
+
+// What one debugger step did
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct StepResult {
+    pub opcode: u8,
+    pub pc_before: usize,
+    pub pc_after: usize,
+    // Gas charged by this instruction, including any child frames
+    pub gas_used: u64,
+    // The frame has stopped, whether during this step or before it
+    pub halted: bool,
+}
+
+// Run arbitrary code against arbitrary calldata. Every stack value used as
+// an offset, length or index is range checked, so no input can panic; this
+// is the entry point for fuzzing. Reverts are reported in the Outcome, any
+// other failure as an error
+pub fn try_run(code: &[u8], calldata: &[u8]) -> Result<Outcome, ExecutionError> {
+    let mut ctx = ExecutionContext::new([0u8; 20], code.to_vec(), calldata.to_vec());
+    let mut tracer = CoverageTracer::new();
+    let reverted = match run_with_tracer(&mut ctx, &mut tracer) {
+        Ok(()) => false,
+        Err(ExecutionError { kind: InstructionError::Revert, .. }) => true,
+        Err(err) => return Err(err),
+    };
+    Ok(Outcome {
+        reverted,
+        return_data: ctx.return_data().clone(),
+        gas_used: ctx.gas().used(),
+        executed_pcs: tracer.into_pcs(),
+    })
+}
+
+// Gas the program uses when given far more than it needs, so callers need
+// not guess a limit. Refunds are not subtracted. Reverts and other failures
+// are errors, since the cost of a failing run is not a useful estimate
+pub fn estimate_gas(code: &[u8], calldata: &[u8]) -> Result<u64, ExecutionError> {
+    let mut ctx = ExecutionContext::builder()
+        .code(code.to_vec())
+        .calldata(calldata.to_vec())
+        .gas_limit(ESTIMATE_GAS_LIMIT)
+        .build();
+    run(&mut ctx)?;
+    Ok(ctx.gas().used())
+}
+
+// Execute instructions from the current PC until the program stops,
+// runs past the end of its code, or an instruction fails
+pub fn run(ctx: &mut ExecutionContext) -> Result<(), ExecutionError> {
+    run_loop(ctx, None)
+}
+
+// Same as run, but reports every step to the tracer
+pub fn run_with_tracer(ctx: &mut ExecutionContext, tracer: &mut dyn Tracer) -> Result<(), ExecutionError> {
+    run_loop(ctx, Some(tracer))
+}
+
+// Storage as a frame found it, so the frame can be undone if it fails
+#[derive(Clone)]
+pub(crate) struct FrameSnapshot {
+    storage: StorageSnapshot,
+    transient_storage: TransientStorage,
+}
+
+// Prepare a frame to run its first instruction. Both run and
+// ExecutionContext::step go through this and end_frame, so stepping through
+// a program charges and changes exactly what running it does
+pub(crate) fn begin_frame(ctx: &mut ExecutionContext) -> FrameSnapshot {
+    if ctx.depth() == 0 {
+        // The transaction's own accounts start out warm (EIP-2929)
+        let accounts = [*ctx.contract_address(), ctx.tx_env().caller, ctx.tx_env().origin];
+        for address in accounts {
+            ctx.access_list_mut().warm_address(address);
+        }
+    }
+    FrameSnapshot {
+        storage: ctx.storage().snapshot(),
+        transient_storage: ctx.transient_storage().clone(),
+    }
+}
+
+// A failed or reverted frame leaves storage exactly as it found it.
+// Self-destructed accounts are only removed when the outermost frame succeeds
+pub(crate) fn end_frame(ctx: &mut ExecutionContext, snapshot: FrameSnapshot, failed: bool) {
+    if failed {
+        ctx.storage_mut().revert_to(snapshot.storage);
+        *ctx.transient_storage_mut() = snapshot.transient_storage;
+    } else if ctx.depth() == 0 {
+        ctx.remove_destroyed_accounts();
+    }
+}
+
+// A frame already started by ExecutionContext::step carries on where the
+// steps left it
+fn run_loop(ctx: &mut ExecutionContext, tracer: Option<&mut dyn Tracer>) -> Result<(), ExecutionError> {
+    let snapshot = match ctx.take_frame() {
+        Some(snapshot) => snapshot,
+        None => begin_frame(ctx),
+    };
+    let result = step_until_halt(ctx, tracer);
+    end_frame(ctx, snapshot, result.is_err());
+    result
+}
+
+// Execute the instruction at the PC and move the PC on as it directs.
+// Past the end of the code this is an implicit STOP
+pub fn step(ctx: &mut ExecutionContext) -> Result<(), InstructionError> {
+    let opcode = ctx.code().get(ctx.pc()).copied().unwrap_or(STOP);
+    match execute_opcode(opcode, ctx)? {
+        Control::Advance(size) => ctx.advance_pc(size),
+        Control::Jumped | Control::Stopped => Ok(()),
+    }
+}
+
+fn step_until_halt(ctx: &mut ExecutionContext, mut tracer: Option<&mut dyn Tracer>) -> Result<(), ExecutionError> {
+    let mut steps: u64 = 0;
+    while !ctx.stopped() && ctx.pc() < ctx.code().len() {
+        let pc = ctx.pc();
+        let opcode = ctx.code()[pc];
+        if ctx.step_limit().is_some_and(|limit| steps >= limit) {
+            return Err(ExecutionError { kind: InstructionError::StepLimitExceeded, pc, opcode });
+        }
+        steps += 1;
+        if let Some(tracer) = tracer.as_mut() {
+            tracer.before_step(ctx);
+        }
+        let result = step(ctx);
+        if let Some(tracer) = tracer.as_mut() {
+            tracer.after_step(ctx, &result);
+        }
+        result.map_err(|kind| ExecutionError { kind, pc, opcode })?;
+    }
+    // Running off the end of the code is an implicit STOP
+    if !ctx.stopped() {
+        ctx.stop();
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::evm::gas::memory_cost;
+    use crate::evm::opcodes::{ADD, JUMP, JUMPDEST, MSTORE, PUSH0, PUSH1, PUSH32, REVERT, SSTORE, STOP};
+    use alloc::vec;
+    use primitive_types::U256;
+
+    // xorshift64, so the programs are the same on every run
+    struct Rng(u64);
+
+    impl Rng {
+        fn next(&mut self) -> u64 {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            self.0
+        }
+
+        fn bytes(&mut self, max_len: u64) -> Vec<u8> {
+            let len = self.next() % (max_len + 1);
+            (0..len).map(|_| self.next() as u8).collect()
+        }
+    }
+
+    #[test]
+    fn random_programs_never_panic() {
+        let mut rng = Rng(0x5eed_1234_abcd_0001);
+        for _ in 0..2000 {
+            let mut code = rng.bytes(64);
+            // Feed in operands that sit at the edges of every range check
+            for word in [[0xffu8; 32], [0u8; 32]] {
+                if rng.next().is_multiple_of(4) {
+                    let at = rng.next() as usize % (code.len() + 1);
+                    let mut push = vec![PUSH32];
+                    push.extend(word);
+                    code.splice(at..at, push);
+                }
+            }
+            let calldata = rng.bytes(40);
+            let _ = try_run(&code, &calldata);
+        }
+    }
+
+    #[test]
+    fn run_leaves_the_result_on_the_stack() {
+        let mut ctx = ExecutionContext::new([0u8; 20], vec![PUSH1, 2, PUSH1, 3, ADD, STOP], vec![]);
+        run(&mut ctx).unwrap();
+        assert!(ctx.stopped());
+        assert_eq!(ctx.stack().peek(0).unwrap(), U256::from(5));
+        assert!(ctx.stack().peek(1).is_err());
+    }
+
+    #[test]
+    fn errors_report_where_they_happened() {
+        // The second ADD underflows
+        let mut ctx = ExecutionContext::new([0u8; 20], vec![PUSH1, 2, PUSH1, 3, ADD, ADD, STOP], vec![]);
+        let err = run(&mut ctx).unwrap_err();
+        assert_eq!((err.pc, err.opcode), (5, ADD));
+        assert!(matches!(err.kind, InstructionError::StackError(_)));
+    }
+
+    // Counts the steps it sees and how many of them succeeded
+    struct CountingTracer {
+        steps: usize,
+        succeeded: usize,
+    }
+
+    impl Tracer for CountingTracer {
+        fn before_step(&mut self, _ctx: &ExecutionContext) {
+            self.steps += 1;
+        }
+
+        fn after_step(&mut self, _ctx: &ExecutionContext, result: &Result<(), InstructionError>) {
+            if result.is_ok() {
+                self.succeeded += 1;
+            }
+        }
+    }
+
+    #[test]
+    fn tracer_sees_every_step() {
+        let mut ctx = ExecutionContext::new([0u8; 20], vec![PUSH1, 2, PUSH1, 3, ADD, STOP], vec![]);
+        let mut tracer = CountingTracer { steps: 0, succeeded: 0 };
+        run_with_tracer(&mut ctx, &mut tracer).unwrap();
+        assert_eq!((tracer.steps, tracer.succeeded), (4, 4));
+
+        // The failing step is reported too
+        let mut ctx = ExecutionContext::new([0u8; 20], vec![PUSH1, 2, ADD], vec![]);
+        let mut tracer = CountingTracer { steps: 0, succeeded: 0 };
+        run_with_tracer(&mut ctx, &mut tracer).unwrap_err();
+        assert_eq!((tracer.steps, tracer.succeeded), (2, 1));
+    }
+
+    #[test]
+    fn step_limit_ends_an_infinite_loop() {
+        let mut ctx = ExecutionContext::builder().code(vec![JUMPDEST, PUSH1, 0, JUMP]).step_limit(100).build();
+        let err = run(&mut ctx).unwrap_err();
+        assert!(matches!(err.kind, InstructionError::StepLimitExceeded));
+        // 100 steps is 33 trips round the loop and the JUMPDEST of the next
+        assert_eq!((err.pc, err.opcode), (1, PUSH1));
+        assert_eq!(ctx.gas().used(), 33 * (1 + 3 + 8) + 1);
+
+        let mut ctx = ExecutionContext::builder().code(vec![PUSH1, 1, STOP]).step_limit(2).build();
+        run(&mut ctx).unwrap();
+    }
+
+    #[test]
+    fn estimate_matches_the_hand_summed_cost() {
+        // A cold SSTORE that sets a slot, then an MSTORE that grows memory to 8 words
+        let code = [PUSH1, 7, PUSH0, SSTORE, PUSH1, 1, PUSH1, 200, MSTORE, STOP];
+        let sstore = 2100 + 20000;
+        assert_eq!(estimate_gas(&code, &[]).unwrap(), 3 + 2 + sstore + 3 + 3 + 3 + memory_cost(8));
+        assert!(estimate_gas(&[PUSH0, PUSH0, REVERT], &[]).is_err());
+    }
+}
@@ -0,0 +1,11 @@
+use crate::evm::context::Address;
+use primitive_types::U256;
+use alloc::vec::Vec;
+
+// An event emitted by one of the LOG0-LOG4 opcodes
+#[derive(Clone)]
+pub struct Log {
+    pub address: Address,
+    pub topics: Vec<U256>,
+    pub data: Vec<u8>,
+}
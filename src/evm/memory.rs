@@ -1,14 +1,52 @@
 use primitive_types::U256;
+use core::fmt;
+use alloc::{vec, vec::Vec};
 
+#[derive(Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Memory {
     memory: Vec<u8>, 
 }
 
+#[derive(Debug)]
 pub enum MemoryError {
     MemoryOverflow,
     MemoryAccessError,
 }
 
+impl fmt::Display for MemoryError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MemoryError::MemoryOverflow => write!(f, "memory overflow"),
+            MemoryError::MemoryAccessError => write!(f, "memory access out of bounds"),
+        }
+    }
+}
+
+impl core::error::Error for MemoryError {}
+
+impl Default for Memory {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// Hex dump with one 32-byte word per row, prefixed by the row's offset
+impl fmt::Debug for Memory {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.memory.is_empty() {
+            return write!(f, "<empty>");
+        }
+        for (row, chunk) in self.memory.chunks(32).enumerate() {
+            if row > 0 {
+                writeln!(f)?;
+            }
+            write!(f, "{:04x}: {}", row * 32, hex::encode(chunk))?;
+        }
+        Ok(())
+    }
+}
+
 impl Memory {
 
     pub fn new() -> Self {
@@ -18,74 +56,212 @@ impl Memory {
     }
 
     pub fn store(&mut self, offset: usize, value: U256) -> Result<(), MemoryError> {
-        
-        let required_size = offset + 32;
-        
-        if required_size > self.memory.len() {
-            self.memory.resize(required_size, 0);
-        }
-
-        let mut bytes = [0u8; 32];
-        value.to_big_endian(&mut bytes);
-        
-
-        for i in 0..32 {
-            self.memory[offset + i] = bytes[i];
-        }
-        
-        Ok(())
+        self.store_range(offset, &value.to_big_endian())
    }
     
 
    pub fn load(&self, offset: usize) -> Result<U256, MemoryError> {
-        // EVM MLOAD loads 32 bytes starting at offset
-        let required_size = offset + 32;
-        
-        if required_size > self.memory.len() {
-            return Ok(U256::zero());
+        // EVM MLOAD loads 32 bytes starting at offset; any of them past the
+        // end of memory read as zero
+        let word = self.load_range(offset, 32)?;
+        Ok(U256::from_big_endian(&word))
+   }
+
+   // Like load, but reading any byte past the end of memory is an error
+   // instead of zero, for tooling that needs to know the data is real
+   pub fn load_checked(&self, offset: usize) -> Result<U256, MemoryError> {
+        let end = offset.checked_add(32).ok_or(MemoryError::MemoryAccessError)?;
+        if end > self.memory.len() {
+            return Err(MemoryError::MemoryAccessError);
         }
+        Ok(U256::from_big_endian(&self.memory[offset..end]))
+   }
 
-        let mut bytes = [0u8; 32];
-        for i in 0..32 {
-            bytes[i] = self.memory[offset + i];
+   // Bytes past the end of memory read as zero, even when the range starts
+   // beyond the current size. Only a range whose end overflows is an error
+   pub fn load_range(&self, offset: usize, length: usize) -> Result<Vec<u8>, MemoryError> {
+        let end = offset.checked_add(length).ok_or(MemoryError::MemoryOverflow)?;
+        let mut result = vec![0u8; length];
+        if offset < self.memory.len() {
+            let end = end.min(self.memory.len());
+            result[..end - offset].copy_from_slice(&self.memory[offset..end]);
         }
-        
-        Ok(U256::from_big_endian(&bytes))
+        Ok(result)
    }
 
-   pub fn load_range(&self, offset: usize, length: usize) -> Vec<u8> {
+   // Write data at offset; the inverse of load_range. Memory grows to the
+   // word boundary past the end of the data, with any gap before offset
+   // zero-filled
+   pub fn store_range(&mut self, offset: usize, data: &[u8]) -> Result<(), MemoryError> {
+        // An empty write must not grow memory, whatever the offset
+        if data.is_empty() {
+            return Ok(());
+        }
+        let end = offset.checked_add(data.len()).ok_or(MemoryError::MemoryOverflow)?;
+        self.expand(end);
+        self.memory[offset..end].copy_from_slice(data);
+        Ok(())
+   }
 
-        let required_size = offset + length; 
+   // Same as store_range
+   pub fn store_bytes(&mut self, offset: usize, data: &[u8]) -> Result<(), MemoryError> {
+        self.store_range(offset, data)
+   }
 
-        if required_size > self.memory.len() {
-            let mut result = self.memory[offset..].to_vec();
-            result.resize(length, 0); 
-            return result; 
+   // Copy length bytes from src to dest within memory. The regions may
+   // overlap; the result is as if the source were read out first
+   pub fn copy_within(&mut self, src: usize, dest: usize, length: usize) -> Result<(), MemoryError> {
+        if length == 0 {
+            return Ok(());
         }
+        let end = src.max(dest).checked_add(length).ok_or(MemoryError::MemoryOverflow)?;
+        self.expand(end);
+        self.memory.copy_within(src..src + length, dest);
+        Ok(())
+   }
 
-        self.memory[offset..offset + length].to_vec()
+   pub fn store_byte(&mut self, offset: usize, byte: u8) -> Result<(), MemoryError> {
+        let end = offset.checked_add(1).ok_or(MemoryError::MemoryOverflow)?;
+        self.expand(end);
+        self.memory[offset] = byte;
+        Ok(())
    }
 
-   pub fn store_bytes(&mut self, offset: usize, data: &[u8]) {
-        let required_size = offset + data.len();
-        if required_size > self.memory.len() {
-            self.memory.resize(required_size, 0);
-        }
-        for (i, &byte) in data.iter().enumerate() {
-            self.memory[offset + i] = byte;
+   // Grow memory with zeros so it covers at least new_size bytes. Memory
+   // always holds whole words, so the new size is rounded up to a multiple
+   // of 32; asking for less than the current size does nothing
+   pub fn expand(&mut self, new_size: usize) {
+        let new_size = new_size.div_ceil(32) * 32;
+        if new_size > self.memory.len() {
+            self.memory.resize(new_size, 0);
         }
    }
 
-   pub fn store_byte(&mut self, offset: usize, byte: u8) {
-        let required_size = offset + 1;
-        if required_size > self.memory.len() {
-            self.memory.resize(required_size, 0);
-        }
-        self.memory[offset] = byte;
+   // Number of 32-byte words currently allocated
+   pub fn word_count(&self) -> usize {
+        self.memory.len().div_ceil(32)
+   }
+
+   // Shrink memory back to size bytes, for undoing an expansion; normal
+   // execution only ever grows it
+   pub fn truncate(&mut self, size: usize) {
+        self.memory.truncate(size);
    }
 
    pub fn size(&self) -> usize {
         self.memory.len()
    }
  
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::format;
+
+    #[test]
+    fn ranges_past_usize_are_errors() {
+        let mut memory = Memory::new();
+        assert!(matches!(memory.load_range(usize::MAX, 2), Err(MemoryError::MemoryOverflow)));
+        assert!(matches!(memory.store_range(usize::MAX, &[1, 2]), Err(MemoryError::MemoryOverflow)));
+        assert!(matches!(memory.store_bytes(usize::MAX - 1, &[1, 2]), Err(MemoryError::MemoryOverflow)));
+        assert!(matches!(memory.store_byte(usize::MAX, 1), Err(MemoryError::MemoryOverflow)));
+        assert!(matches!(memory.copy_within(usize::MAX, 0, 2), Err(MemoryError::MemoryOverflow)));
+        assert!(matches!(memory.copy_within(0, usize::MAX, 2), Err(MemoryError::MemoryOverflow)));
+        assert_eq!(memory.size(), 0);
+    }
+
+    #[test]
+    fn empty_ranges_never_overflow() {
+        let mut memory = Memory::new();
+        assert_eq!(memory.load_range(usize::MAX, 0).unwrap(), Vec::<u8>::new());
+        memory.store_range(usize::MAX, &[]).unwrap();
+        memory.copy_within(usize::MAX, usize::MAX, 0).unwrap();
+        assert_eq!(memory.size(), 0);
+    }
+
+    #[test]
+    fn store_byte_past_the_end_zero_fills() {
+        let mut memory = Memory::new();
+        memory.store_byte(5, 0xaa).unwrap();
+        assert_eq!(memory.size(), 32);
+        assert_eq!(memory.load_range(0, 6).unwrap(), [0, 0, 0, 0, 0, 0xaa]);
+    }
+
+    #[test]
+    fn store_bytes_grows_to_fit() {
+        let mut memory = Memory::new();
+        memory.store_bytes(30, &[1, 2, 3]).unwrap();
+        assert_eq!(memory.size(), 64);
+        assert_eq!(memory.load_range(29, 4).unwrap(), [0, 1, 2, 3]);
+    }
+
+    #[test]
+    fn store_bytes_of_nothing_does_not_grow() {
+        let mut memory = Memory::new();
+        memory.store_bytes(100, &[]).unwrap();
+        assert_eq!(memory.size(), 0);
+    }
+
+    #[test]
+    fn load_range_pads_past_the_end() {
+        let mut memory = Memory::new();
+        memory.store_bytes(0, &[1, 2, 3, 4]).unwrap();
+        assert_eq!(memory.load_range(1, 2).unwrap(), [2, 3]);
+        assert_eq!(memory.load_range(30, 4).unwrap(), [0, 0, 0, 0]);
+        memory.store_bytes(28, &[5, 6, 7, 8]).unwrap();
+        assert_eq!(memory.load_range(30, 4).unwrap(), [7, 8, 0, 0]);
+        // Reading does not grow memory
+        assert_eq!(memory.size(), 32);
+    }
+
+    #[test]
+    fn debug_is_a_hex_dump_of_32_byte_rows() {
+        let mut memory = Memory::new();
+        assert_eq!(format!("{:?}", memory), "<empty>");
+        memory.store_byte(33, 0xab).unwrap();
+        let zeros = "00".repeat(32);
+        let second = format!("00ab{}", "00".repeat(30));
+        assert_eq!(format!("{:?}", memory), format!("0000: {}\n0020: {}", zeros, second));
+    }
+
+    #[test]
+    fn expand_rounds_up_and_never_shrinks() {
+        let mut memory = Memory::new();
+        assert_eq!(memory.word_count(), 0);
+        memory.expand(1);
+        assert_eq!(memory.size(), 32);
+        memory.expand(33);
+        assert_eq!((memory.size(), memory.word_count()), (64, 2));
+        memory.expand(10);
+        assert_eq!(memory.size(), 64);
+        memory.store(64, U256::one()).unwrap();
+        assert_eq!(memory.word_count(), 3);
+    }
+
+    #[test]
+    fn load_checked_errors_where_load_pads() {
+        let mut memory = Memory::new();
+        memory.store(0, U256::from(5)).unwrap();
+        assert_eq!(memory.load_checked(0).unwrap(), U256::from(5));
+        assert!(matches!(memory.load_checked(1), Err(MemoryError::MemoryAccessError)));
+        assert!(matches!(memory.load_checked(usize::MAX), Err(MemoryError::MemoryAccessError)));
+        assert_eq!(memory.load(1).unwrap(), U256::from(5) << 8);
+        assert_eq!(memory.load(100).unwrap(), U256::zero());
+    }
+
+    #[test]
+    fn store_range_across_a_word_boundary() {
+        let mut memory = Memory::new();
+        let data: Vec<u8> = (1..=10).collect();
+        memory.store_range(28, &data).unwrap();
+        // Bytes 28..38 need a second word
+        assert_eq!(memory.size(), 64);
+        assert_eq!(memory.load_range(28, 10).unwrap(), data);
+        assert_eq!(memory.load_range(0, 28).unwrap(), [0; 28]);
+        assert_eq!(memory.load_range(38, 26).unwrap(), [0; 26]);
+        // Ending exactly on a boundary does not add a word
+        memory.store_range(96, &[0xff; 32]).unwrap();
+        assert_eq!(memory.size(), 128);
+    }
+}
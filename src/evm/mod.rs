@@ -0,0 +1,26 @@
+pub mod access_list;
+pub mod assembler;
+pub mod calldata;
+pub mod collections;
+pub mod constants;
+pub mod context;
+pub mod coverage;
+pub mod disasm;
+pub mod env;
+pub mod gas;
+pub mod hardfork;
+pub mod hash;
+pub(crate) mod history;
+pub mod instruction;
+pub mod interpreter;
+pub mod log;
+pub mod memory;
+pub mod opcodes;
+pub mod precompiles;
+pub mod rlp;
+pub(crate) mod signed;
+pub mod stack;
+pub mod state;
+pub mod storage;
+pub mod tracer;
+pub mod transaction;
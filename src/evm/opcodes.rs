@@ -1,13 +1,87 @@
-use context::ExecutionContext;
-use primitive_types::U256;
-use stack::StackError;
-use memory::MemoryError;
+#![allow(non_snake_case)]
 
+use crate::evm::constants::CALL_DEPTH_LIMIT;
+use crate::evm::context::{Address, ExecStatus, ExecutionContext};
+use crate::evm::gas::{
+    copy_cost, exp_cost, sha3_cost, sstore_cost, Gas, CALL_STIPEND, CALL_VALUE_COST, COLD_ACCOUNT_ACCESS_COST, COLD_SLOAD_COST, SSTORE_SENTRY,
+    WARM_STORAGE_READ_COST,
+};
+use crate::evm::interpreter::{run, ExecutionError};
+use crate::evm::gas::static_gas_cost;
+use crate::evm::hardfork::Hardfork;
+use crate::evm::hash::keccak256;
+use crate::evm::instruction::{instructions, opcode_info};
+use crate::evm::log::Log;
+use crate::evm::precompiles::{is_precompile, run_precompile};
+use crate::evm::rlp::rlp_encode_address_nonce;
+use primitive_types::{U256, U512};
+use crate::evm::stack::{Stack, StackError};
+use crate::evm::memory::MemoryError;
+use crate::evm::storage::StorageError;
+use crate::evm::signed::{abs, is_negative, neg, to_signed_cmp};
+use core::cmp::Ordering;
+use crate::evm::collections::Set;
+use core::fmt;
+use alloc::{vec, vec::Vec};
+
+#[derive(Debug)]
 pub enum InstructionError {
     InvalidOpcode,
     StackError(StackError),
     MemoryError(MemoryError),
+    StorageError(StorageError),
     InvalidJump,
+    // Execution hit REVERT; the revert data is left in the context's return data
+    Revert,
+    OutOfGas,
+    // The designated INVALID (0xfe) instruction was executed
+    InvalidInstruction,
+    // A stack value used as an offset or length does not fit in memory
+    OutOfBounds,
+    // A state-changing instruction was executed inside a STATICCALL
+    StaticModeViolation,
+    // The run executed more instructions than its step limit allows
+    StepLimitExceeded,
+}
+
+impl fmt::Display for InstructionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            InstructionError::InvalidOpcode => write!(f, "invalid opcode"),
+            InstructionError::StackError(err) => write!(f, "{}", err),
+            InstructionError::MemoryError(err) => write!(f, "{}", err),
+            InstructionError::StorageError(err) => write!(f, "{}", err),
+            InstructionError::InvalidJump => write!(f, "invalid jump destination"),
+            InstructionError::Revert => write!(f, "execution reverted"),
+            InstructionError::OutOfGas => write!(f, "out of gas"),
+            InstructionError::InvalidInstruction => write!(f, "invalid instruction"),
+            InstructionError::OutOfBounds => write!(f, "offset or length out of bounds"),
+            InstructionError::StaticModeViolation => write!(f, "state modification in static call"),
+            InstructionError::StepLimitExceeded => write!(f, "step limit exceeded"),
+        }
+    }
+}
+
+impl core::error::Error for InstructionError {
+    fn source(&self) -> Option<&(dyn core::error::Error + 'static)> {
+        match self {
+            InstructionError::StackError(err) => Some(err),
+            InstructionError::MemoryError(err) => Some(err),
+            InstructionError::StorageError(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+// What the run loop does with the PC after an instruction succeeds
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Control {
+    // Move on past an instruction of this many bytes
+    Advance(usize),
+    // The instruction already set the PC
+    Jumped,
+    // The frame halted; the PC stays on the halting instruction
+    Stopped,
 }
 
 // Stop and Arithmetic
@@ -16,18 +90,28 @@ pub const ADD: u8 = 0x01;
 pub const MUL: u8 = 0x02;
 pub const SUB: u8 = 0x03;
 pub const DIV: u8 = 0x04;
+pub const SDIV: u8 = 0x05;
 pub const MOD: u8 = 0x06;
+pub const SMOD: u8 = 0x07;
+pub const ADDMOD: u8 = 0x08;
+pub const MULMOD: u8 = 0x09;
 pub const EXP: u8 = 0x0a;
 
 // Comparison & Bitwise
 pub const LT: u8 = 0x10;
 pub const GT: u8 = 0x11;
+pub const SLT: u8 = 0x12;
+pub const SGT: u8 = 0x13;
 pub const EQ: u8 = 0x14;
 pub const ISZERO: u8 = 0x15;
 pub const AND: u8 = 0x16;
 pub const OR: u8 = 0x17;
 pub const XOR: u8 = 0x18;
 pub const NOT: u8 = 0x19;
+pub const BYTE: u8 = 0x1a;
+pub const SHL: u8 = 0x1b;
+pub const SHR: u8 = 0x1c;
+pub const SAR: u8 = 0x1d;
 
 // SHA3
 pub const SHA3: u8 = 0x20;
@@ -46,13 +130,18 @@ pub const CODECOPY: u8 = 0x39;
 pub const GASPRICE: u8 = 0x3a;
 pub const EXTCODESIZE: u8 = 0x3b;
 pub const EXTCODECOPY: u8 = 0x3c;
+pub const EXTCODEHASH: u8 = 0x3f;
 
 // Block Information
 pub const BLOCKHASH: u8 = 0x40;
 pub const COINBASE: u8 = 0x41;
 pub const TIMESTAMP: u8 = 0x42;
 pub const NUMBER: u8 = 0x43;
+pub const PREVRANDAO: u8 = 0x44;
 pub const GASLIMIT: u8 = 0x45;
+pub const CHAINID: u8 = 0x46;
+pub const SELFBALANCE: u8 = 0x47;
+pub const BASEFEE: u8 = 0x48;
 
 // Storage, Memory and Flow Operations
 pub const POP: u8 = 0x50;
@@ -67,8 +156,12 @@ pub const PC: u8 = 0x58;
 pub const MSIZE: u8 = 0x59;
 pub const GAS: u8 = 0x5a;
 pub const JUMPDEST: u8 = 0x5b;
+pub const TLOAD: u8 = 0x5c;
+pub const TSTORE: u8 = 0x5d;
+pub const MCOPY: u8 = 0x5e;
 
-// Push Operations (0x60-0x7f)
+// Push Operations (0x5f-0x7f)
+pub const PUSH0: u8 = 0x5f;
 pub const PUSH1: u8 = 0x60;
 pub const PUSH2: u8 = 0x61;
 pub const PUSH3: u8 = 0x62;
@@ -77,6 +170,29 @@ pub const PUSH5: u8 = 0x64;
 pub const PUSH6: u8 = 0x65;
 pub const PUSH7: u8 = 0x66;
 pub const PUSH8: u8 = 0x67;
+pub const PUSH9: u8 = 0x68;
+pub const PUSH10: u8 = 0x69;
+pub const PUSH11: u8 = 0x6a;
+pub const PUSH12: u8 = 0x6b;
+pub const PUSH13: u8 = 0x6c;
+pub const PUSH14: u8 = 0x6d;
+pub const PUSH15: u8 = 0x6e;
+pub const PUSH16: u8 = 0x6f;
+pub const PUSH17: u8 = 0x70;
+pub const PUSH18: u8 = 0x71;
+pub const PUSH19: u8 = 0x72;
+pub const PUSH20: u8 = 0x73;
+pub const PUSH21: u8 = 0x74;
+pub const PUSH22: u8 = 0x75;
+pub const PUSH23: u8 = 0x76;
+pub const PUSH24: u8 = 0x77;
+pub const PUSH25: u8 = 0x78;
+pub const PUSH26: u8 = 0x79;
+pub const PUSH27: u8 = 0x7a;
+pub const PUSH28: u8 = 0x7b;
+pub const PUSH29: u8 = 0x7c;
+pub const PUSH30: u8 = 0x7d;
+pub const PUSH31: u8 = 0x7e;
 pub const PUSH32: u8 = 0x7f;
 
 // Dup Operations (0x80-0x8f)
@@ -84,12 +200,36 @@ pub const DUP1: u8 = 0x80;
 pub const DUP2: u8 = 0x81;
 pub const DUP3: u8 = 0x82;
 pub const DUP4: u8 = 0x83;
+pub const DUP5: u8 = 0x84;
+pub const DUP6: u8 = 0x85;
+pub const DUP7: u8 = 0x86;
+pub const DUP8: u8 = 0x87;
+pub const DUP9: u8 = 0x88;
+pub const DUP10: u8 = 0x89;
+pub const DUP11: u8 = 0x8a;
+pub const DUP12: u8 = 0x8b;
+pub const DUP13: u8 = 0x8c;
+pub const DUP14: u8 = 0x8d;
+pub const DUP15: u8 = 0x8e;
+pub const DUP16: u8 = 0x8f;
 
 // Swap Operations (0x90-0x9f)
 pub const SWAP1: u8 = 0x90;
 pub const SWAP2: u8 = 0x91;
 pub const SWAP3: u8 = 0x92;
 pub const SWAP4: u8 = 0x93;
+pub const SWAP5: u8 = 0x94;
+pub const SWAP6: u8 = 0x95;
+pub const SWAP7: u8 = 0x96;
+pub const SWAP8: u8 = 0x97;
+pub const SWAP9: u8 = 0x98;
+pub const SWAP10: u8 = 0x99;
+pub const SWAP11: u8 = 0x9a;
+pub const SWAP12: u8 = 0x9b;
+pub const SWAP13: u8 = 0x9c;
+pub const SWAP14: u8 = 0x9d;
+pub const SWAP15: u8 = 0x9e;
+pub const SWAP16: u8 = 0x9f;
 
 // Logging
 pub const LOG0: u8 = 0xa0;
@@ -103,10 +243,30 @@ pub const CREATE: u8 = 0xf0;
 pub const CALL: u8 = 0xf1;
 pub const RETURN: u8 = 0xf3;
 pub const DELEGATECALL: u8 = 0xf4;
+pub const STATICCALL: u8 = 0xfa;
 pub const REVERT: u8 = 0xfd;
+pub const INVALID: u8 = 0xfe;
 pub const SELFDESTRUCT: u8 = 0xff;
 
-pub fn execute_opcode(opcode: u8, ctx: &mut ExecutionContext) -> Result<(), InstructionError> {
+pub fn execute_opcode(opcode: u8, ctx: &mut ExecutionContext) -> Result<Control, InstructionError> {
+    // Opcodes from later forks are undefined under earlier ones
+    if !ctx.hardfork().is_active(opcode) {
+        return Err(InstructionError::InvalidOpcode);
+    }
+    // Check the stack up front, as the EVM does, so a failing instruction
+    // leaves it untouched
+    if let Some(info) = opcode_info(opcode) {
+        let len = ctx.stack().len();
+        if len < info.stack_in as usize {
+            return Err(InstructionError::StackError(StackError::StackUnderflow));
+        }
+        if len - info.stack_in as usize + info.stack_out as usize > ctx.stack().max_depth() {
+            return Err(InstructionError::StackError(StackError::StackOverflow));
+        }
+    }
+    let cost = static_gas_cost(opcode, ctx.hardfork());
+    ctx.gas_mut().charge(cost)?;
+    
     match opcode {
         // Stop
         STOP => handleStop(ctx),
@@ -116,12 +276,18 @@ pub fn execute_opcode(opcode: u8, ctx: &mut ExecutionContext) -> Result<(), Inst
         MUL => handleMul(ctx),
         SUB => handleSub(ctx),
         DIV => handleDiv(ctx),
+        SDIV => handleSdiv(ctx),
         MOD => handleMod(ctx),
+        SMOD => handleSmod(ctx),
+        ADDMOD => handleAddmod(ctx),
+        MULMOD => handleMulmod(ctx),
         EXP => handleExp(ctx),
         
         // Comparison
         LT => handleLt(ctx),
         GT => handleGt(ctx),
+        SLT => handleSlt(ctx),
+        SGT => handleSgt(ctx),
         EQ => handleEq(ctx),
         ISZERO => handleIsZero(ctx),
         
@@ -130,43 +296,47 @@ pub fn execute_opcode(opcode: u8, ctx: &mut ExecutionContext) -> Result<(), Inst
         OR => handleOr(ctx),
         XOR => handleXor(ctx),
         NOT => handleNot(ctx),
+        BYTE => handleByte(ctx),
+        SHL => handleShl(ctx),
+        SHR => handleShr(ctx),
+        SAR => handleSar(ctx),
+        
+        // SHA3
+        SHA3 => handleSha3(ctx),
         
         // Stack
         POP => handlePop(ctx),
-        DUP1 => handleDup1(ctx),
-        DUP2 => handleDup2(ctx),
-        DUP3 => handleDup3(ctx),
-        DUP4 => handleDup4(ctx),
-        SWAP1 => handleSwap1(ctx),
-        SWAP2 => handleSwap2(ctx),
-        SWAP3 => handleSwap3(ctx),
-        SWAP4 => handleSwap4(ctx),
+        DUP1..=DUP16 => handleDup(ctx, (opcode - DUP1 + 1) as usize),
+        SWAP1..=SWAP16 => handleSwap(ctx, (opcode - SWAP1 + 1) as usize),
         
         // Memory
         MLOAD => handleMload(ctx),
         MSTORE => handleMstore(ctx),
         MSTORE8 => handleMstore8(ctx),
         MSIZE => handleMsize(ctx),
+        MCOPY => handleMcopy(ctx),
+        
+        // Storage
+        SLOAD => handleSload(ctx),
+        SSTORE => handleSstore(ctx),
+        TLOAD => handleTload(ctx),
+        TSTORE => handleTstore(ctx),
         
         // Control Flow
         JUMP => handleJump(ctx),
         JUMPI => handleJumpi(ctx),
         JUMPDEST => handleJumpdest(ctx),
         PC => handlePc(ctx),
+        GAS => handleGas(ctx),
         
         // Push Operations
-        PUSH1 => handlePush1(ctx),
-        PUSH2 => handlePush2(ctx),
-        PUSH3 => handlePush3(ctx),
-        PUSH4 => handlePush4(ctx),
-        PUSH5 => handlePush5(ctx),
-        PUSH6 => handlePush6(ctx),
-        PUSH7 => handlePush7(ctx),
-        PUSH8 => handlePush8(ctx),
-        PUSH32 => handlePush32(ctx),
+        PUSH0 => handlePush0(ctx),
+        PUSH1..=PUSH32 => handlePush(ctx, (opcode - PUSH1 + 1) as usize),
         
         // Environment
         ADDRESS => handleAddress(ctx),
+        BALANCE => handleBalance(ctx),
+        ORIGIN => handleOrigin(ctx),
         CALLER => handleCaller(ctx),
         CALLVALUE => handleCallvalue(ctx),
         CALLDATALOAD => handleCalldataload(ctx),
@@ -174,70 +344,170 @@ pub fn execute_opcode(opcode: u8, ctx: &mut ExecutionContext) -> Result<(), Inst
         CALLDATACOPY => handleCalldatacopy(ctx),
         CODESIZE => handleCodesize(ctx),
         CODECOPY => handleCodecopy(ctx),
+        GASPRICE => handleGasprice(ctx),
+        EXTCODESIZE => handleExtcodesize(ctx),
+        EXTCODECOPY => handleExtcodecopy(ctx),
+        EXTCODEHASH => handleExtcodehash(ctx),
+        
+        // Block Information
+        BLOCKHASH => handleBlockhash(ctx),
+        COINBASE => handleCoinbase(ctx),
+        TIMESTAMP => handleTimestamp(ctx),
+        NUMBER => handleNumber(ctx),
+        PREVRANDAO => handlePrevrandao(ctx),
+        GASLIMIT => handleGaslimit(ctx),
+        CHAINID => handleChainid(ctx),
+        SELFBALANCE => handleSelfbalance(ctx),
+        BASEFEE => handleBasefee(ctx),
+        
+        // Logging
+        LOG0..=LOG4 => handleLog(ctx, (opcode - LOG0) as usize),
         
         // Return
         RETURN => handleReturn(ctx),
+        REVERT => handleRevert(ctx),
+        CREATE => handleCreate(ctx),
+        CALL => handleCall(ctx),
+        DELEGATECALL => handleDelegatecall(ctx),
+        STATICCALL => handleStaticcall(ctx),
+        SELFDESTRUCT => handleSelfdestruct(ctx),
+        INVALID => handleInvalid(ctx),
         
         _ => Err(InstructionError::InvalidOpcode),
     }
 }
 
+// Convert a stack word used as an offset or length, failing instead of
+// panicking when it cannot be addressed
+pub fn to_usize(value: U256) -> Result<usize, InstructionError> {
+    if value > U256::from(usize::MAX) {
+        return Err(InstructionError::OutOfBounds);
+    }
+    Ok(value.as_usize())
+}
+
+// Offsets into read-only sources (code, calldata) past their end all read
+// as zeros, so they can be clamped to the source length
+fn clamp_to_usize(value: U256, max: usize) -> usize {
+    if value > U256::from(max) { max } else { value.as_usize() }
+}
+
 // Stop
-fn handleStop(ctx: &mut ExecutionContext) -> Result<(), InstructionError> {
+fn handleStop(ctx: &mut ExecutionContext) -> Result<Control, InstructionError> {
     ctx.stop();
-    ctx.set_pc(ctx.pc() + 1);
-    Ok(())
+    Ok(Control::Stopped)
 }
 
 // Arithmetic Operations
-fn handleAdd(ctx: &mut ExecutionContext) -> Result<(), InstructionError> {
-    let b = ctx.stack_mut().pop().map_err(InstructionError::StackError)?;
+// Operands are named in stack order: `a` is the top item, `b` the one below
+// it, so SUB computes a - b and LT tests a < b, as in the yellow paper
+fn handleAdd(ctx: &mut ExecutionContext) -> Result<Control, InstructionError> {
     let a = ctx.stack_mut().pop().map_err(InstructionError::StackError)?;
+    let b = ctx.stack_mut().pop().map_err(InstructionError::StackError)?;
     let result = a.overflowing_add(b).0;
     ctx.stack_mut().push(result).map_err(InstructionError::StackError)?;
-    ctx.set_pc(ctx.pc() + 1);
-    Ok(())
+    Ok(Control::Advance(1))
 }
 
-fn handleMul(ctx: &mut ExecutionContext) -> Result<(), InstructionError> {
-    let b = ctx.stack_mut().pop().map_err(InstructionError::StackError)?;
+fn handleMul(ctx: &mut ExecutionContext) -> Result<Control, InstructionError> {
     let a = ctx.stack_mut().pop().map_err(InstructionError::StackError)?;
+    let b = ctx.stack_mut().pop().map_err(InstructionError::StackError)?;
     let result = a.overflowing_mul(b).0;
     ctx.stack_mut().push(result).map_err(InstructionError::StackError)?;
-    ctx.set_pc(ctx.pc() + 1);
-    Ok(())
+    Ok(Control::Advance(1))
 }
 
-fn handleSub(ctx: &mut ExecutionContext) -> Result<(), InstructionError> {
-    let b = ctx.stack_mut().pop().map_err(InstructionError::StackError)?;
+fn handleSub(ctx: &mut ExecutionContext) -> Result<Control, InstructionError> {
     let a = ctx.stack_mut().pop().map_err(InstructionError::StackError)?;
+    let b = ctx.stack_mut().pop().map_err(InstructionError::StackError)?;
     let result = a.overflowing_sub(b).0;
     ctx.stack_mut().push(result).map_err(InstructionError::StackError)?;
-    ctx.set_pc(ctx.pc() + 1);
-    Ok(())
+    Ok(Control::Advance(1))
 }
 
-fn handleDiv(ctx: &mut ExecutionContext) -> Result<(), InstructionError> {
-    let b = ctx.stack_mut().pop().map_err(InstructionError::StackError)?;
+fn handleDiv(ctx: &mut ExecutionContext) -> Result<Control, InstructionError> {
     let a = ctx.stack_mut().pop().map_err(InstructionError::StackError)?;
+    let b = ctx.stack_mut().pop().map_err(InstructionError::StackError)?;
     let result = if b.is_zero() { U256::zero() } else { a / b };
     ctx.stack_mut().push(result).map_err(InstructionError::StackError)?;
-    ctx.set_pc(ctx.pc() + 1);
-    Ok(())
+    Ok(Control::Advance(1))
 }
 
-fn handleMod(ctx: &mut ExecutionContext) -> Result<(), InstructionError> {
+fn handleSdiv(ctx: &mut ExecutionContext) -> Result<Control, InstructionError> {
+    let a = ctx.stack_mut().pop().map_err(InstructionError::StackError)?;
     let b = ctx.stack_mut().pop().map_err(InstructionError::StackError)?;
+    // Divide magnitudes and reapply the sign, which truncates toward zero.
+    // MIN_I256 / -1 wraps back to MIN_I256 through the final negation.
+    let result = if b.is_zero() {
+        U256::zero()
+    } else {
+        let quotient = abs(a) / abs(b);
+        if is_negative(a) != is_negative(b) { neg(quotient) } else { quotient }
+    };
+    ctx.stack_mut().push(result).map_err(InstructionError::StackError)?;
+    Ok(Control::Advance(1))
+}
+
+fn handleMod(ctx: &mut ExecutionContext) -> Result<Control, InstructionError> {
     let a = ctx.stack_mut().pop().map_err(InstructionError::StackError)?;
+    let b = ctx.stack_mut().pop().map_err(InstructionError::StackError)?;
     let result = if b.is_zero() { U256::zero() } else { a % b };
     ctx.stack_mut().push(result).map_err(InstructionError::StackError)?;
-    ctx.set_pc(ctx.pc() + 1);
-    Ok(())
+    Ok(Control::Advance(1))
 }
 
-fn handleExp(ctx: &mut ExecutionContext) -> Result<(), InstructionError> {
-    let exponent = ctx.stack_mut().pop().map_err(InstructionError::StackError)?;
+fn handleSmod(ctx: &mut ExecutionContext) -> Result<Control, InstructionError> {
+    let a = ctx.stack_mut().pop().map_err(InstructionError::StackError)?;
+    let b = ctx.stack_mut().pop().map_err(InstructionError::StackError)?;
+    // The remainder takes the sign of the dividend
+    let result = if b.is_zero() {
+        U256::zero()
+    } else {
+        let remainder = abs(a) % abs(b);
+        if is_negative(a) { neg(remainder) } else { remainder }
+    };
+    ctx.stack_mut().push(result).map_err(InstructionError::StackError)?;
+    Ok(Control::Advance(1))
+}
+
+fn handleAddmod(ctx: &mut ExecutionContext) -> Result<Control, InstructionError> {
+    let a = ctx.stack_mut().pop().map_err(InstructionError::StackError)?;
+    let b = ctx.stack_mut().pop().map_err(InstructionError::StackError)?;
+    let n = ctx.stack_mut().pop().map_err(InstructionError::StackError)?;
+    // Widen to 512 bits so the intermediate sum cannot wrap before reducing
+    let result = if n.is_zero() {
+        U256::zero()
+    } else {
+        narrow((U512::from(a) + U512::from(b)) % U512::from(n))
+    };
+    ctx.stack_mut().push(result).map_err(InstructionError::StackError)?;
+    Ok(Control::Advance(1))
+}
+
+fn handleMulmod(ctx: &mut ExecutionContext) -> Result<Control, InstructionError> {
+    let a = ctx.stack_mut().pop().map_err(InstructionError::StackError)?;
+    let b = ctx.stack_mut().pop().map_err(InstructionError::StackError)?;
+    let n = ctx.stack_mut().pop().map_err(InstructionError::StackError)?;
+    // The full product of two 256-bit words always fits in 512 bits
+    let result = if n.is_zero() {
+        U256::zero()
+    } else {
+        narrow(a.full_mul(b) % U512::from(n))
+    };
+    ctx.stack_mut().push(result).map_err(InstructionError::StackError)?;
+    Ok(Control::Advance(1))
+}
+
+// Values reduced modulo a U256 always fit back into a U256
+fn narrow(value: U512) -> U256 {
+    U256::try_from(value).expect("value reduced below a 256-bit modulus")
+}
+
+fn handleExp(ctx: &mut ExecutionContext) -> Result<Control, InstructionError> {
     let base = ctx.stack_mut().pop().map_err(InstructionError::StackError)?;
+    let exponent = ctx.stack_mut().pop().map_err(InstructionError::StackError)?;
+    let cost = exp_cost(exponent, ctx.hardfork());
+    ctx.gas_mut().charge(cost)?;
     // U256 doesn't have pow, so we use a simple implementation
     // For large exponents, this could be optimized
     let result = if exponent.is_zero() {
@@ -254,420 +524,1090 @@ fn handleExp(ctx: &mut ExecutionContext) -> Result<(), InstructionError> {
                 result = result.overflowing_mul(base_pow).0;
             }
             base_pow = base_pow.overflowing_mul(base_pow).0;
-            exp = exp >> 1;
+            exp >>= 1;
         }
         result
     };
     ctx.stack_mut().push(result).map_err(InstructionError::StackError)?;
-    ctx.set_pc(ctx.pc() + 1);
-    Ok(())
+    Ok(Control::Advance(1))
 }
 
 // Comparison Operations
-fn handleLt(ctx: &mut ExecutionContext) -> Result<(), InstructionError> {
-    let b = ctx.stack_mut().pop().map_err(InstructionError::StackError)?;
+fn handleLt(ctx: &mut ExecutionContext) -> Result<Control, InstructionError> {
     let a = ctx.stack_mut().pop().map_err(InstructionError::StackError)?;
+    let b = ctx.stack_mut().pop().map_err(InstructionError::StackError)?;
     let result = if a < b { U256::from(1) } else { U256::zero() };
     ctx.stack_mut().push(result).map_err(InstructionError::StackError)?;
-    ctx.set_pc(ctx.pc() + 1);
-    Ok(())
+    Ok(Control::Advance(1))
 }
 
-fn handleGt(ctx: &mut ExecutionContext) -> Result<(), InstructionError> {
-    let b = ctx.stack_mut().pop().map_err(InstructionError::StackError)?;
+fn handleGt(ctx: &mut ExecutionContext) -> Result<Control, InstructionError> {
     let a = ctx.stack_mut().pop().map_err(InstructionError::StackError)?;
+    let b = ctx.stack_mut().pop().map_err(InstructionError::StackError)?;
     let result = if a > b { U256::from(1) } else { U256::zero() };
     ctx.stack_mut().push(result).map_err(InstructionError::StackError)?;
-    ctx.set_pc(ctx.pc() + 1);
-    Ok(())
+    Ok(Control::Advance(1))
 }
 
-fn handleEq(ctx: &mut ExecutionContext) -> Result<(), InstructionError> {
+fn handleSlt(ctx: &mut ExecutionContext) -> Result<Control, InstructionError> {
+    let a = ctx.stack_mut().pop().map_err(InstructionError::StackError)?;
     let b = ctx.stack_mut().pop().map_err(InstructionError::StackError)?;
+    let result = if to_signed_cmp(a, b) == Ordering::Less { U256::from(1) } else { U256::zero() };
+    ctx.stack_mut().push(result).map_err(InstructionError::StackError)?;
+    Ok(Control::Advance(1))
+}
+
+fn handleSgt(ctx: &mut ExecutionContext) -> Result<Control, InstructionError> {
     let a = ctx.stack_mut().pop().map_err(InstructionError::StackError)?;
-    let result = if a == b { U256::from(1) } else { U256::zero() };
+    let b = ctx.stack_mut().pop().map_err(InstructionError::StackError)?;
+    let result = if to_signed_cmp(a, b) == Ordering::Greater { U256::from(1) } else { U256::zero() };
     ctx.stack_mut().push(result).map_err(InstructionError::StackError)?;
-    ctx.set_pc(ctx.pc() + 1);
-    Ok(())
+    Ok(Control::Advance(1))
 }
 
-fn handleIsZero(ctx: &mut ExecutionContext) -> Result<(), InstructionError> {
+fn handleEq(ctx: &mut ExecutionContext) -> Result<Control, InstructionError> {
     let a = ctx.stack_mut().pop().map_err(InstructionError::StackError)?;
-    let result = if a.is_zero() { U256::from(1) } else { U256::zero() };
+    let b = ctx.stack_mut().pop().map_err(InstructionError::StackError)?;
+    let result = if a == b { U256::from(1) } else { U256::zero() };
     ctx.stack_mut().push(result).map_err(InstructionError::StackError)?;
-    ctx.set_pc(ctx.pc() + 1);
-    Ok(())
+    Ok(Control::Advance(1))
+}
+
+// The operand of an instruction that rewrites the top of the stack in place;
+// an empty stack is an underflow, as it would be for pop
+fn top_operand(ctx: &ExecutionContext) -> Result<U256, InstructionError> {
+    ctx.stack().peek(0).map_err(|_| InstructionError::StackError(StackError::StackUnderflow))
+}
+
+fn handleIsZero(ctx: &mut ExecutionContext) -> Result<Control, InstructionError> {
+    let a = top_operand(ctx)?;
+    let result = if a.is_zero() { U256::from(1) } else { U256::zero() };
+    ctx.stack_mut().replace_top(result).map_err(InstructionError::StackError)?;
+    Ok(Control::Advance(1))
 }
 
 // Bitwise Operations
-fn handleAnd(ctx: &mut ExecutionContext) -> Result<(), InstructionError> {
-    let b = ctx.stack_mut().pop().map_err(InstructionError::StackError)?;
+fn handleAnd(ctx: &mut ExecutionContext) -> Result<Control, InstructionError> {
     let a = ctx.stack_mut().pop().map_err(InstructionError::StackError)?;
+    let b = ctx.stack_mut().pop().map_err(InstructionError::StackError)?;
     let result = a & b;
     ctx.stack_mut().push(result).map_err(InstructionError::StackError)?;
-    ctx.set_pc(ctx.pc() + 1);
-    Ok(())
+    Ok(Control::Advance(1))
 }
 
-fn handleOr(ctx: &mut ExecutionContext) -> Result<(), InstructionError> {
-    let b = ctx.stack_mut().pop().map_err(InstructionError::StackError)?;
+fn handleOr(ctx: &mut ExecutionContext) -> Result<Control, InstructionError> {
     let a = ctx.stack_mut().pop().map_err(InstructionError::StackError)?;
+    let b = ctx.stack_mut().pop().map_err(InstructionError::StackError)?;
     let result = a | b;
     ctx.stack_mut().push(result).map_err(InstructionError::StackError)?;
-    ctx.set_pc(ctx.pc() + 1);
-    Ok(())
+    Ok(Control::Advance(1))
 }
 
-fn handleXor(ctx: &mut ExecutionContext) -> Result<(), InstructionError> {
-    let b = ctx.stack_mut().pop().map_err(InstructionError::StackError)?;
+fn handleXor(ctx: &mut ExecutionContext) -> Result<Control, InstructionError> {
     let a = ctx.stack_mut().pop().map_err(InstructionError::StackError)?;
+    let b = ctx.stack_mut().pop().map_err(InstructionError::StackError)?;
     let result = a ^ b;
     ctx.stack_mut().push(result).map_err(InstructionError::StackError)?;
-    ctx.set_pc(ctx.pc() + 1);
-    Ok(())
+    Ok(Control::Advance(1))
 }
 
-fn handleNot(ctx: &mut ExecutionContext) -> Result<(), InstructionError> {
-    let a = ctx.stack_mut().pop().map_err(InstructionError::StackError)?;
-    let result = !a;
-    ctx.stack_mut().push(result).map_err(InstructionError::StackError)?;
-    ctx.set_pc(ctx.pc() + 1);
-    Ok(())
+fn handleNot(ctx: &mut ExecutionContext) -> Result<Control, InstructionError> {
+    let a = top_operand(ctx)?;
+    ctx.stack_mut().replace_top(!a).map_err(InstructionError::StackError)?;
+    Ok(Control::Advance(1))
 }
 
-// Stack Operations
-fn handlePop(ctx: &mut ExecutionContext) -> Result<(), InstructionError> {
-    ctx.stack_mut().pop().map_err(InstructionError::StackError)?;
-    ctx.set_pc(ctx.pc() + 1);
-    Ok(())
+fn handleByte(ctx: &mut ExecutionContext) -> Result<Control, InstructionError> {
+    let i = ctx.stack_mut().pop().map_err(InstructionError::StackError)?;
+    let x = ctx.stack_mut().pop().map_err(InstructionError::StackError)?;
+    // Byte 0 is the most significant byte of the word
+    let result = if i < U256::from(32) {
+        U256::from(x.to_big_endian()[i.as_usize()])
+    } else {
+        U256::zero()
+    };
+    ctx.stack_mut().push(result).map_err(InstructionError::StackError)?;
+    Ok(Control::Advance(1))
 }
 
-fn handleDup1(ctx: &mut ExecutionContext) -> Result<(), InstructionError> {
-    let value = ctx.stack().peek(0).map_err(InstructionError::StackError)?;
-    ctx.stack_mut().push(value).map_err(InstructionError::StackError)?;
-    ctx.set_pc(ctx.pc() + 1);
-    Ok(())
+fn handleShl(ctx: &mut ExecutionContext) -> Result<Control, InstructionError> {
+    let shift = ctx.stack_mut().pop().map_err(InstructionError::StackError)?;
+    let value = top_operand(ctx)?;
+    let result = if shift < U256::from(256) { value << shift.as_usize() } else { U256::zero() };
+    ctx.stack_mut().replace_top(result).map_err(InstructionError::StackError)?;
+    Ok(Control::Advance(1))
 }
 
-fn handleDup2(ctx: &mut ExecutionContext) -> Result<(), InstructionError> {
-    let value = ctx.stack().peek(1).map_err(InstructionError::StackError)?;
-    ctx.stack_mut().push(value).map_err(InstructionError::StackError)?;
-    ctx.set_pc(ctx.pc() + 1);
-    Ok(())
+fn handleShr(ctx: &mut ExecutionContext) -> Result<Control, InstructionError> {
+    let shift = ctx.stack_mut().pop().map_err(InstructionError::StackError)?;
+    let value = top_operand(ctx)?;
+    let result = if shift < U256::from(256) { value >> shift.as_usize() } else { U256::zero() };
+    ctx.stack_mut().replace_top(result).map_err(InstructionError::StackError)?;
+    Ok(Control::Advance(1))
 }
 
-fn handleDup3(ctx: &mut ExecutionContext) -> Result<(), InstructionError> {
-    let value = ctx.stack().peek(2).map_err(InstructionError::StackError)?;
-    ctx.stack_mut().push(value).map_err(InstructionError::StackError)?;
-    ctx.set_pc(ctx.pc() + 1);
-    Ok(())
-}
-
-fn handleDup4(ctx: &mut ExecutionContext) -> Result<(), InstructionError> {
-    let value = ctx.stack().peek(3).map_err(InstructionError::StackError)?;
-    ctx.stack_mut().push(value).map_err(InstructionError::StackError)?;
-    ctx.set_pc(ctx.pc() + 1);
-    Ok(())
+fn handleSar(ctx: &mut ExecutionContext) -> Result<Control, InstructionError> {
+    let shift = ctx.stack_mut().pop().map_err(InstructionError::StackError)?;
+    let value = top_operand(ctx)?;
+    // Shift the bitwise complement of negative values so the vacated high
+    // bits fill with ones once it is complemented back
+    let result = if shift < U256::from(256) {
+        let shift = shift.as_usize();
+        if is_negative(value) { !(!value >> shift) } else { value >> shift }
+    } else if is_negative(value) {
+        U256::MAX
+    } else {
+        U256::zero()
+    };
+    ctx.stack_mut().replace_top(result).map_err(InstructionError::StackError)?;
+    Ok(Control::Advance(1))
 }
 
-fn handleSwap1(ctx: &mut ExecutionContext) -> Result<(), InstructionError> {
-    ctx.stack_mut().swap(1).map_err(InstructionError::StackError)?;
-    ctx.set_pc(ctx.pc() + 1);
-    Ok(())
+// SHA3
+fn handleSha3(ctx: &mut ExecutionContext) -> Result<Control, InstructionError> {
+    let offset = ctx.stack_mut().pop().map_err(InstructionError::StackError)?;
+    let length = ctx.stack_mut().pop().map_err(InstructionError::StackError)?;
+    
+    // A zero-length hash never touches memory
+    let data = if length.is_zero() {
+        Vec::new()
+    } else {
+        let offset = to_usize(offset)?;
+        let length = to_usize(length)?;
+        // Charged before memory grows, so an absurd length runs out of gas
+        // instead of allocating
+        ctx.gas_mut().charge(sha3_cost(length))?;
+        ctx.expand_memory(offset, length)?;
+        ctx.memory().load_range(offset, length).map_err(|_| InstructionError::OutOfBounds)?
+    };
+    
+    let hash = U256::from_big_endian(&keccak256(&data));
+    ctx.stack_mut().push(hash).map_err(InstructionError::StackError)?;
+    Ok(Control::Advance(1))
 }
 
-fn handleSwap2(ctx: &mut ExecutionContext) -> Result<(), InstructionError> {
-    ctx.stack_mut().swap(2).map_err(InstructionError::StackError)?;
-    ctx.set_pc(ctx.pc() + 1);
-    Ok(())
+// Stack Operations
+fn handlePop(ctx: &mut ExecutionContext) -> Result<Control, InstructionError> {
+    ctx.stack_mut().pop().map_err(InstructionError::StackError)?;
+    Ok(Control::Advance(1))
 }
 
-fn handleSwap3(ctx: &mut ExecutionContext) -> Result<(), InstructionError> {
-    ctx.stack_mut().swap(3).map_err(InstructionError::StackError)?;
-    ctx.set_pc(ctx.pc() + 1);
-    Ok(())
+// DUPn copies the item n-1 positions below the top onto the stack
+fn handleDup(ctx: &mut ExecutionContext, n: usize) -> Result<Control, InstructionError> {
+    ctx.stack_mut().dup(n - 1).map_err(InstructionError::StackError)?;
+    Ok(Control::Advance(1))
 }
 
-fn handleSwap4(ctx: &mut ExecutionContext) -> Result<(), InstructionError> {
-    ctx.stack_mut().swap(4).map_err(InstructionError::StackError)?;
-    ctx.set_pc(ctx.pc() + 1);
-    Ok(())
+// SWAPn exchanges the top item with the one n positions below it
+fn handleSwap(ctx: &mut ExecutionContext, n: usize) -> Result<Control, InstructionError> {
+    ctx.stack_mut().swap(n).map_err(InstructionError::StackError)?;
+    Ok(Control::Advance(1))
 }
 
 // Memory Operations
-fn handleMload(ctx: &mut ExecutionContext) -> Result<(), InstructionError> {
+fn handleMload(ctx: &mut ExecutionContext) -> Result<Control, InstructionError> {
     let offset = ctx.stack_mut().pop().map_err(InstructionError::StackError)?;
-    let value = ctx.memory().load(offset.as_usize())
-        .map_err(InstructionError::MemoryError)?;
+    let offset = to_usize(offset)?;
+    ctx.expand_memory(offset, 32)?;
+    // Memory now covers the word, so a read past its end is a real range
+    // violation rather than something to paper over with zeros
+    let value = ctx.memory().load_checked(offset)
+        .map_err(|_| InstructionError::OutOfBounds)?;
     ctx.stack_mut().push(value).map_err(InstructionError::StackError)?;
-    ctx.set_pc(ctx.pc() + 1);
-    Ok(())
+    Ok(Control::Advance(1))
 }
 
-fn handleMstore(ctx: &mut ExecutionContext) -> Result<(), InstructionError> {
+fn handleMstore(ctx: &mut ExecutionContext) -> Result<Control, InstructionError> {
     let offset = ctx.stack_mut().pop().map_err(InstructionError::StackError)?;
     let value = ctx.stack_mut().pop().map_err(InstructionError::StackError)?;
-    ctx.memory_mut().store(offset.as_usize(), value)
-        .map_err(InstructionError::MemoryError)?;
-    ctx.set_pc(ctx.pc() + 1);
-    Ok(())
+    let offset = to_usize(offset)?;
+    ctx.expand_memory(offset, 32)?;
+    ctx.memory_mut().store(offset, value)
+        .map_err(|_| InstructionError::OutOfBounds)?;
+    Ok(Control::Advance(1))
 }
 
-fn handleMstore8(ctx: &mut ExecutionContext) -> Result<(), InstructionError> {
+fn handleMstore8(ctx: &mut ExecutionContext) -> Result<Control, InstructionError> {
     let offset = ctx.stack_mut().pop().map_err(InstructionError::StackError)?;
     let value = ctx.stack_mut().pop().map_err(InstructionError::StackError)?;
     // MSTORE8 stores only the least significant byte
     let byte = (value & U256::from(0xff)).as_u32() as u8;
-    ctx.memory_mut().store_byte(offset.as_usize(), byte);
-    ctx.set_pc(ctx.pc() + 1);
-    Ok(())
+    let offset = to_usize(offset)?;
+    ctx.expand_memory(offset, 1)?;
+    ctx.memory_mut().store_byte(offset, byte).map_err(|_| InstructionError::OutOfBounds)?;
+    Ok(Control::Advance(1))
 }
 
-fn handleMsize(ctx: &mut ExecutionContext) -> Result<(), InstructionError> {
-    let size = ctx.memory().size();
+fn handleMsize(ctx: &mut ExecutionContext) -> Result<Control, InstructionError> {
+    // Memory is measured in whole 32-byte words
+    let size = ctx.memory().word_count() * 32;
     ctx.stack_mut().push(U256::from(size)).map_err(InstructionError::StackError)?;
-    ctx.set_pc(ctx.pc() + 1);
-    Ok(())
+    Ok(Control::Advance(1))
 }
 
-// Control Flow
-fn handleJump(ctx: &mut ExecutionContext) -> Result<(), InstructionError> {
+// Copy a region of memory to another, possibly overlapping one (EIP-5656)
+fn handleMcopy(ctx: &mut ExecutionContext) -> Result<Control, InstructionError> {
     let dest = ctx.stack_mut().pop().map_err(InstructionError::StackError)?;
-    let dest_usize = dest.as_usize();
+    let src = ctx.stack_mut().pop().map_err(InstructionError::StackError)?;
+    let length = ctx.stack_mut().pop().map_err(InstructionError::StackError)?;
     
-    // Validate jump destination
-    if dest_usize >= ctx.code().len() {
-        return Err(InstructionError::InvalidJump);
+    if length.is_zero() {
+        return Ok(Control::Advance(1));
+    }
+    let dest = to_usize(dest)?;
+    let src = to_usize(src)?;
+    let length = to_usize(length)?;
+    ctx.gas_mut().charge(copy_cost(length))?;
+    // Memory grows to cover whichever region ends later
+    ctx.expand_memory(src.max(dest), length)?;
+    
+    ctx.memory_mut().copy_within(src, dest, length).map_err(|_| InstructionError::OutOfBounds)?;
+    Ok(Control::Advance(1))
+}
+
+// Storage Operations
+// Charge the cold surcharge the first time a slot of this contract is read
+// or written, then mark it warm
+fn access_slot(ctx: &mut ExecutionContext, slot: U256) -> Result<(), InstructionError> {
+    let address = *ctx.contract_address();
+    if ctx.hardfork().has_access_lists() && !ctx.access_list().is_warm_slot(&address, slot) {
+        ctx.gas_mut().charge(COLD_SLOAD_COST - WARM_STORAGE_READ_COST)?;
+        ctx.access_list_mut().warm_slot(address, slot);
+    }
+    Ok(())
+}
+
+fn handleSload(ctx: &mut ExecutionContext) -> Result<Control, InstructionError> {
+    let slot = ctx.stack_mut().pop().map_err(InstructionError::StackError)?;
+    access_slot(ctx, slot)?;
+    let value = ctx.storage().load(slot).map_err(InstructionError::StorageError)?;
+    ctx.stack_mut().push(value).map_err(InstructionError::StackError)?;
+    Ok(Control::Advance(1))
+}
+
+// Priced by sstore_cost, which depends on the fork and on the slot's value
+// before the transaction as well as now
+fn handleSstore(ctx: &mut ExecutionContext) -> Result<Control, InstructionError> {
+    ensure_not_static(ctx)?;
+    // EIP-2200 makes SSTORE fail outright unless enough gas is left for a call stipend
+    if ctx.hardfork() >= Hardfork::Istanbul && ctx.gas().remaining() <= SSTORE_SENTRY {
+        return Err(InstructionError::OutOfGas);
+    }
+    let slot = ctx.stack_mut().pop().map_err(InstructionError::StackError)?;
+    let value = ctx.stack_mut().pop().map_err(InstructionError::StackError)?;
+    
+    // Unlike SLOAD the write cost does not include a warm read, so a cold
+    // slot pays the full cold cost on top
+    let address = *ctx.contract_address();
+    if ctx.hardfork().has_access_lists() && !ctx.access_list_mut().warm_slot(address, slot) {
+        ctx.gas_mut().charge(COLD_SLOAD_COST)?;
     }
+    let current = ctx.storage().load(slot).map_err(InstructionError::StorageError)?;
+    let original = *ctx.original_storage_mut().entry((address, slot)).or_insert(current);
+    
+    let (cost, refund) = sstore_cost(original, current, value, ctx.hardfork());
+    ctx.gas_mut().charge(cost)?;
+    ctx.add_gas_refund(refund);
+    ctx.storage_mut().store(slot, value).map_err(InstructionError::StorageError)?;
+    Ok(Control::Advance(1))
+}
+
+// Transient storage (EIP-1153) is kept per contract and lives for one
+// transaction only
+fn handleTload(ctx: &mut ExecutionContext) -> Result<Control, InstructionError> {
+    let slot = ctx.stack_mut().pop().map_err(InstructionError::StackError)?;
+    let address = *ctx.contract_address();
+    let value = ctx.transient_storage().load(&address, slot).map_err(InstructionError::StorageError)?;
+    ctx.stack_mut().push(value).map_err(InstructionError::StackError)?;
+    Ok(Control::Advance(1))
+}
+
+fn handleTstore(ctx: &mut ExecutionContext) -> Result<Control, InstructionError> {
+    ensure_not_static(ctx)?;
+    let slot = ctx.stack_mut().pop().map_err(InstructionError::StackError)?;
+    let value = ctx.stack_mut().pop().map_err(InstructionError::StackError)?;
+    let address = *ctx.contract_address();
+    ctx.transient_storage_mut().store(address, slot, value).map_err(InstructionError::StorageError)?;
+    Ok(Control::Advance(1))
+}
+
+// Control Flow
+// Positions of JUMPDEST opcodes, skipping 0x5b bytes that are PUSH immediates
+pub fn valid_jumpdests(code: &[u8]) -> Set<usize> {
+    instructions(code)
+        .filter(|instruction| instruction.opcode == JUMPDEST)
+        .map(|instruction| instruction.pc)
+        .collect()
+}
+
+fn handleJump(ctx: &mut ExecutionContext) -> Result<Control, InstructionError> {
+    let dest = ctx.stack_mut().pop().map_err(InstructionError::StackError)?;
+    let dest_usize = to_usize(dest).map_err(|_| InstructionError::InvalidJump)?;
     
-    // Check if destination is JUMPDEST
-    if ctx.code()[dest_usize] != JUMPDEST {
+    if !ctx.is_valid_jumpdest(dest_usize) {
         return Err(InstructionError::InvalidJump);
     }
     
     ctx.set_pc(dest_usize);
-    Ok(())
+    Ok(Control::Jumped)
 }
 
-fn handleJumpi(ctx: &mut ExecutionContext) -> Result<(), InstructionError> {
+fn handleJumpi(ctx: &mut ExecutionContext) -> Result<Control, InstructionError> {
     let dest = ctx.stack_mut().pop().map_err(InstructionError::StackError)?;
     let condition = ctx.stack_mut().pop().map_err(InstructionError::StackError)?;
     
     if !condition.is_zero() {
         // Condition is true, perform jump
-        let dest_usize = dest.as_usize();
-        
-        if dest_usize >= ctx.code().len() {
-            return Err(InstructionError::InvalidJump);
-        }
+        let dest_usize = to_usize(dest).map_err(|_| InstructionError::InvalidJump)?;
         
-        if ctx.code()[dest_usize] != JUMPDEST {
+        if !ctx.is_valid_jumpdest(dest_usize) {
             return Err(InstructionError::InvalidJump);
         }
         
         ctx.set_pc(dest_usize);
+        Ok(Control::Jumped)
     } else {
-        // Condition is false, just advance PC
-        ctx.set_pc(ctx.pc() + 1);
+        // Condition is false, fall through to the next instruction
+        Ok(Control::Advance(1))
     }
-    
-    Ok(())
 }
 
-fn handleJumpdest(ctx: &mut ExecutionContext) -> Result<(), InstructionError> {
-    // JUMPDEST is a no-op, just advance PC
-    ctx.set_pc(ctx.pc() + 1);
-    Ok(())
+fn handleJumpdest(_ctx: &mut ExecutionContext) -> Result<Control, InstructionError> {
+    // JUMPDEST only marks a jump target
+    Ok(Control::Advance(1))
 }
 
-fn handlePc(ctx: &mut ExecutionContext) -> Result<(), InstructionError> {
+fn handlePc(ctx: &mut ExecutionContext) -> Result<Control, InstructionError> {
     let pc_value = U256::from(ctx.pc());
     ctx.stack_mut().push(pc_value).map_err(InstructionError::StackError)?;
-    ctx.set_pc(ctx.pc() + 1);
-    Ok(())
+    Ok(Control::Advance(1))
 }
 
-// Push Operations
-fn handlePush1(ctx: &mut ExecutionContext) -> Result<(), InstructionError> {
-    let value_byte = ctx.read_code(1)[0];
-    let value = U256::from(value_byte);
-    ctx.stack_mut().push(value).map_err(InstructionError::StackError)?;
-    ctx.set_pc(ctx.pc() + 2);
-    Ok(())
+// The GAS instruction's own cost has already been charged by the time
+// the remaining gas is read
+fn handleGas(ctx: &mut ExecutionContext) -> Result<Control, InstructionError> {
+    let remaining = U256::from(ctx.gas().remaining());
+    ctx.stack_mut().push(remaining).map_err(InstructionError::StackError)?;
+    Ok(Control::Advance(1))
 }
 
-fn handlePush2(ctx: &mut ExecutionContext) -> Result<(), InstructionError> {
-    let bytes = ctx.read_code(2);
-    let value = U256::from_big_endian(&bytes);
-    ctx.stack_mut().push(value).map_err(InstructionError::StackError)?;
-    ctx.set_pc(ctx.pc() + 3);
-    Ok(())
+// Push Operations
+fn handlePush0(ctx: &mut ExecutionContext) -> Result<Control, InstructionError> {
+    ctx.stack_mut().push(U256::zero()).map_err(InstructionError::StackError)?;
+    Ok(Control::Advance(1))
 }
 
-fn handlePush3(ctx: &mut ExecutionContext) -> Result<(), InstructionError> {
-    let bytes = ctx.read_code(3);
-    let mut padded = vec![0u8; 32];
-    padded[29..].copy_from_slice(&bytes);
-    let value = U256::from_big_endian(&padded);
+// PUSHn reads the n immediate bytes that follow the opcode as a big-endian
+// word. An immediate cut off by the end of the code is zero-padded, and the
+// context flags it
+fn handlePush(ctx: &mut ExecutionContext, n: usize) -> Result<Control, InstructionError> {
+    // Make sure the PC can move past the immediate before touching the stack
+    if ctx.pc().checked_add(n + 1).is_none() {
+        return Err(InstructionError::OutOfBounds);
+    }
+    let (bytes, available) = ctx.read_code_with_len(n + 1);
+    let value = U256::from_big_endian(&bytes[1..]);
     ctx.stack_mut().push(value).map_err(InstructionError::StackError)?;
-    ctx.set_pc(ctx.pc() + 4);
-    Ok(())
+    if available < n + 1 {
+        ctx.set_truncated_push(true);
+    }
+    Ok(Control::Advance(n + 1))
 }
 
-fn handlePush4(ctx: &mut ExecutionContext) -> Result<(), InstructionError> {
-    let bytes = ctx.read_code(4);
-    let mut padded = vec![0u8; 32];
-    padded[28..].copy_from_slice(&bytes);
-    let value = U256::from_big_endian(&padded);
-    ctx.stack_mut().push(value).map_err(InstructionError::StackError)?;
-    ctx.set_pc(ctx.pc() + 5);
+// Environment Operations
+// Charge the cold surcharge the first time an account is touched, then mark
+// it warm
+fn access_account(ctx: &mut ExecutionContext, address: Address) -> Result<(), InstructionError> {
+    // Nothing is cold before Berlin, and precompiles are always warm
+    if !ctx.hardfork().has_access_lists() || is_precompile(&address) {
+        return Ok(());
+    }
+    if !ctx.access_list().is_warm_address(&address) {
+        ctx.gas_mut().charge(COLD_ACCOUNT_ACCESS_COST - WARM_STORAGE_READ_COST)?;
+        ctx.access_list_mut().warm_address(address);
+    }
     Ok(())
 }
 
-fn handlePush5(ctx: &mut ExecutionContext) -> Result<(), InstructionError> {
-    let bytes = ctx.read_code(5);
-    let mut padded = vec![0u8; 32];
-    padded[27..].copy_from_slice(&bytes);
-    let value = U256::from_big_endian(&padded);
-    ctx.stack_mut().push(value).map_err(InstructionError::StackError)?;
-    ctx.set_pc(ctx.pc() + 6);
-    Ok(())
+// Addresses are pushed as words, left-padded with zeros
+fn address_to_word(addr: &Address) -> U256 {
+    let mut bytes = [0u8; 32];
+    bytes[12..].copy_from_slice(addr);
+    U256::from_big_endian(&bytes)
 }
 
-fn handlePush6(ctx: &mut ExecutionContext) -> Result<(), InstructionError> {
-    let bytes = ctx.read_code(6);
-    let mut padded = vec![0u8; 32];
-    padded[26..].copy_from_slice(&bytes);
-    let value = U256::from_big_endian(&padded);
-    ctx.stack_mut().push(value).map_err(InstructionError::StackError)?;
-    ctx.set_pc(ctx.pc() + 7);
-    Ok(())
+// The low 20 bytes of a stack word name an address
+pub(crate) fn word_to_address(word: U256) -> Address {
+    let mut addr = [0u8; 20];
+    addr.copy_from_slice(&word.to_big_endian()[12..]);
+    addr
 }
 
-fn handlePush7(ctx: &mut ExecutionContext) -> Result<(), InstructionError> {
-    let bytes = ctx.read_code(7);
-    let mut padded = vec![0u8; 32];
-    padded[25..].copy_from_slice(&bytes);
-    let value = U256::from_big_endian(&padded);
-    ctx.stack_mut().push(value).map_err(InstructionError::StackError)?;
-    ctx.set_pc(ctx.pc() + 8);
-    Ok(())
+// Bytes [offset, offset + length) of source, zero-padded past its end
+pub(crate) fn padded_slice(source: &[u8], offset: usize, length: usize) -> Vec<u8> {
+    let mut bytes = vec![0u8; length];
+    if offset < source.len() {
+        let end = offset.saturating_add(length).min(source.len());
+        bytes[..end - offset].copy_from_slice(&source[offset..end]);
+    }
+    bytes
 }
 
-fn handlePush8(ctx: &mut ExecutionContext) -> Result<(), InstructionError> {
-    let bytes = ctx.read_code(8);
-    let mut padded = vec![0u8; 32];
-    padded[24..].copy_from_slice(&bytes);
-    let value = U256::from_big_endian(&padded);
+fn handleAddress(ctx: &mut ExecutionContext) -> Result<Control, InstructionError> {
+    let value = address_to_word(ctx.contract_address());
     ctx.stack_mut().push(value).map_err(InstructionError::StackError)?;
-    ctx.set_pc(ctx.pc() + 9);
-    Ok(())
+    Ok(Control::Advance(1))
 }
 
-fn handlePush32(ctx: &mut ExecutionContext) -> Result<(), InstructionError> {
-    let bytes = ctx.read_code(32);
-    let value = U256::from_big_endian(&bytes);
-    ctx.stack_mut().push(value).map_err(InstructionError::StackError)?;
-    ctx.set_pc(ctx.pc() + 33);
-    Ok(())
+fn handleBalance(ctx: &mut ExecutionContext) -> Result<Control, InstructionError> {
+    let address = word_to_address(ctx.stack_mut().pop().map_err(InstructionError::StackError)?);
+    access_account(ctx, address)?;
+    let balance = ctx.state().balance(&address);
+    ctx.stack_mut().push(balance).map_err(InstructionError::StackError)?;
+    Ok(Control::Advance(1))
 }
 
-// Environment Operations
-fn handleAddress(ctx: &mut ExecutionContext) -> Result<(), InstructionError> {
-    let addr = ctx.contract_address();
-    let mut bytes = [0u8; 32];
-    bytes[12..].copy_from_slice(addr);
-    let value = U256::from_big_endian(&bytes);
+fn handleOrigin(ctx: &mut ExecutionContext) -> Result<Control, InstructionError> {
+    let value = address_to_word(&ctx.tx_env().origin);
     ctx.stack_mut().push(value).map_err(InstructionError::StackError)?;
-    ctx.set_pc(ctx.pc() + 1);
-    Ok(())
+    Ok(Control::Advance(1))
 }
 
-fn handleCaller(ctx: &mut ExecutionContext) -> Result<(), InstructionError> {
-    // TODO: CALLER should come from transaction context, not contract address
-    // For now, return zero
-    ctx.stack_mut().push(U256::zero()).map_err(InstructionError::StackError)?;
-    ctx.set_pc(ctx.pc() + 1);
-    Ok(())
+fn handleCaller(ctx: &mut ExecutionContext) -> Result<Control, InstructionError> {
+    let value = address_to_word(&ctx.tx_env().caller);
+    ctx.stack_mut().push(value).map_err(InstructionError::StackError)?;
+    Ok(Control::Advance(1))
 }
 
-fn handleCallvalue(ctx: &mut ExecutionContext) -> Result<(), InstructionError> {
-    // TODO: CALLVALUE should come from transaction context
-    // For now, return zero
-    ctx.stack_mut().push(U256::zero()).map_err(InstructionError::StackError)?;
-    ctx.set_pc(ctx.pc() + 1);
-    Ok(())
+fn handleCallvalue(ctx: &mut ExecutionContext) -> Result<Control, InstructionError> {
+    let value = ctx.tx_env().value;
+    ctx.stack_mut().push(value).map_err(InstructionError::StackError)?;
+    Ok(Control::Advance(1))
 }
 
-fn handleCalldataload(ctx: &mut ExecutionContext) -> Result<(), InstructionError> {
+fn handleCalldataload(ctx: &mut ExecutionContext) -> Result<Control, InstructionError> {
     let offset = ctx.stack_mut().pop().map_err(InstructionError::StackError)?;
-    let value = ctx.calldata().load(offset.as_usize());
+    // Any offset past the end reads zeros, including ones too large for a
+    // usize, so clamp instead of failing
+    let offset = clamp_to_usize(offset, ctx.calldata().size());
+    let value = ctx.calldata().load(offset);
     ctx.stack_mut().push(value).map_err(InstructionError::StackError)?;
-    ctx.set_pc(ctx.pc() + 1);
-    Ok(())
+    Ok(Control::Advance(1))
 }
 
-fn handleCalldatasize(ctx: &mut ExecutionContext) -> Result<(), InstructionError> {
+fn handleCalldatasize(ctx: &mut ExecutionContext) -> Result<Control, InstructionError> {
     let size = ctx.calldata().size();
     ctx.stack_mut().push(U256::from(size)).map_err(InstructionError::StackError)?;
-    ctx.set_pc(ctx.pc() + 1);
-    Ok(())
+    Ok(Control::Advance(1))
 }
 
-fn handleCalldatacopy(ctx: &mut ExecutionContext) -> Result<(), InstructionError> {
+fn handleCalldatacopy(ctx: &mut ExecutionContext) -> Result<Control, InstructionError> {
     let mem_offset = ctx.stack_mut().pop().map_err(InstructionError::StackError)?;
     let calldata_offset = ctx.stack_mut().pop().map_err(InstructionError::StackError)?;
     let length = ctx.stack_mut().pop().map_err(InstructionError::StackError)?;
     
-    ctx.calldata().copy_to_memory(
-        calldata_offset.as_usize(),
-        mem_offset.as_usize(),
-        length.as_usize(),
-        ctx.memory_mut()
-    ).map_err(|_| InstructionError::InvalidOpcode)?;
+    if length.is_zero() {
+        return Ok(Control::Advance(1));
+    }
+    let calldata_offset = clamp_to_usize(calldata_offset, ctx.calldata().size());
+    let length = to_usize(length)?;
+    let mem_offset = to_usize(mem_offset)?;
+    ctx.gas_mut().charge(copy_cost(length))?;
+    ctx.expand_memory(mem_offset, length)?;
     
-    ctx.set_pc(ctx.pc() + 1);
-    Ok(())
+    // Bytes past the end of the calldata are copied as zeros
+    let (calldata, memory) = ctx.calldata_and_memory_mut();
+    calldata.copy_to_memory(calldata_offset, mem_offset, length, memory)
+        .map_err(|_| InstructionError::OutOfBounds)?;
+    Ok(Control::Advance(1))
 }
 
-fn handleCodesize(ctx: &mut ExecutionContext) -> Result<(), InstructionError> {
+fn handleCodesize(ctx: &mut ExecutionContext) -> Result<Control, InstructionError> {
     let size = ctx.code().len();
     ctx.stack_mut().push(U256::from(size)).map_err(InstructionError::StackError)?;
-    ctx.set_pc(ctx.pc() + 1);
-    Ok(())
+    Ok(Control::Advance(1))
 }
 
-fn handleCodecopy(ctx: &mut ExecutionContext) -> Result<(), InstructionError> {
+fn handleCodecopy(ctx: &mut ExecutionContext) -> Result<Control, InstructionError> {
     let mem_offset = ctx.stack_mut().pop().map_err(InstructionError::StackError)?;
     let code_offset = ctx.stack_mut().pop().map_err(InstructionError::StackError)?;
     let length = ctx.stack_mut().pop().map_err(InstructionError::StackError)?;
     
-    let code_offset_usize = code_offset.as_usize();
-    let length_usize = length.as_usize();
-    let mem_offset_usize = mem_offset.as_usize();
-    
-    // Copy code to memory
-    let mut bytes = Vec::new();
-    for i in 0..length_usize {
-        if code_offset_usize + i < ctx.code().len() {
-            bytes.push(ctx.code()[code_offset_usize + i]);
-        } else {
-            bytes.push(0);
-        }
+    if length.is_zero() {
+        return Ok(Control::Advance(1));
     }
+    let code_offset = clamp_to_usize(code_offset, ctx.code().len());
+    let length = to_usize(length)?;
+    let mem_offset = to_usize(mem_offset)?;
+    // Gas is charged before anything is allocated, so an absurd length runs
+    // out of gas instead of building a huge buffer
+    ctx.gas_mut().charge(copy_cost(length))?;
+    ctx.expand_memory(mem_offset, length)?;
     
-    ctx.memory_mut().store_bytes(mem_offset_usize, &bytes);
-    ctx.set_pc(ctx.pc() + 1);
-    Ok(())
+    let bytes = padded_slice(ctx.code(), code_offset, length);
+    ctx.memory_mut().store_range(mem_offset, &bytes).map_err(|_| InstructionError::OutOfBounds)?;
+    Ok(Control::Advance(1))
+}
+
+fn handleGasprice(ctx: &mut ExecutionContext) -> Result<Control, InstructionError> {
+    let value = ctx.tx_env().gas_price;
+    ctx.stack_mut().push(value).map_err(InstructionError::StackError)?;
+    Ok(Control::Advance(1))
+}
+
+fn handleExtcodesize(ctx: &mut ExecutionContext) -> Result<Control, InstructionError> {
+    let address = word_to_address(ctx.stack_mut().pop().map_err(InstructionError::StackError)?);
+    access_account(ctx, address)?;
+    let size = ctx.state().code(&address).len();
+    ctx.stack_mut().push(U256::from(size)).map_err(InstructionError::StackError)?;
+    Ok(Control::Advance(1))
+}
+
+fn handleExtcodecopy(ctx: &mut ExecutionContext) -> Result<Control, InstructionError> {
+    let address = word_to_address(ctx.stack_mut().pop().map_err(InstructionError::StackError)?);
+    access_account(ctx, address)?;
+    let mem_offset = ctx.stack_mut().pop().map_err(InstructionError::StackError)?;
+    let code_offset = ctx.stack_mut().pop().map_err(InstructionError::StackError)?;
+    let length = ctx.stack_mut().pop().map_err(InstructionError::StackError)?;
+    
+    if !length.is_zero() {
+        let mem_offset = to_usize(mem_offset)?;
+        let length = to_usize(length)?;
+        ctx.gas_mut().charge(copy_cost(length))?;
+        ctx.expand_memory(mem_offset, length)?;
+        // Unknown accounts have no code, so the copy is all zeros
+        let code = ctx.state().code(&address);
+        let bytes = padded_slice(code, clamp_to_usize(code_offset, code.len()), length);
+        ctx.memory_mut().store_range(mem_offset, &bytes).map_err(|_| InstructionError::OutOfBounds)?;
+    }
+    Ok(Control::Advance(1))
+}
+
+fn handleExtcodehash(ctx: &mut ExecutionContext) -> Result<Control, InstructionError> {
+    let address = word_to_address(ctx.stack_mut().pop().map_err(InstructionError::StackError)?);
+    access_account(ctx, address)?;
+    // Zero for an account that does not exist or is empty; an account that
+    // exists without code still hashes its code, giving keccak256 of nothing
+    let hash = match ctx.state().account(&address) {
+        Some(account) if !account.is_empty() => U256::from_big_endian(&keccak256(&account.code)),
+        _ => U256::zero(),
+    };
+    ctx.stack_mut().push(hash).map_err(InstructionError::StackError)?;
+    Ok(Control::Advance(1))
+}
+
+// Block Information
+fn handleBlockhash(ctx: &mut ExecutionContext) -> Result<Control, InstructionError> {
+    let number = ctx.stack_mut().pop().map_err(InstructionError::StackError)?;
+    let current = ctx.block_env().number;
+    // Only the 256 most recent blocks are available; the current block
+    // and future blocks always hash to zero
+    let in_window = number < current && current - number <= U256::from(256);
+    let hash = if in_window {
+        // Only reachable with number below the current block, but the current
+        // block number itself is an arbitrary word
+        u64::try_from(number).ok()
+            .and_then(|number| ctx.block_env().block_hashes.get(&number).copied())
+            .unwrap_or_default()
+    } else {
+        U256::zero()
+    };
+    ctx.stack_mut().push(hash).map_err(InstructionError::StackError)?;
+    Ok(Control::Advance(1))
+}
+
+fn handleCoinbase(ctx: &mut ExecutionContext) -> Result<Control, InstructionError> {
+    let value = address_to_word(&ctx.block_env().coinbase);
+    ctx.stack_mut().push(value).map_err(InstructionError::StackError)?;
+    Ok(Control::Advance(1))
+}
+
+fn handleTimestamp(ctx: &mut ExecutionContext) -> Result<Control, InstructionError> {
+    let value = ctx.block_env().timestamp;
+    ctx.stack_mut().push(value).map_err(InstructionError::StackError)?;
+    Ok(Control::Advance(1))
+}
+
+fn handleNumber(ctx: &mut ExecutionContext) -> Result<Control, InstructionError> {
+    let value = ctx.block_env().number;
+    ctx.stack_mut().push(value).map_err(InstructionError::StackError)?;
+    Ok(Control::Advance(1))
+}
+
+fn handlePrevrandao(ctx: &mut ExecutionContext) -> Result<Control, InstructionError> {
+    let value = ctx.block_env().prev_randao;
+    ctx.stack_mut().push(value).map_err(InstructionError::StackError)?;
+    Ok(Control::Advance(1))
+}
+
+fn handleGaslimit(ctx: &mut ExecutionContext) -> Result<Control, InstructionError> {
+    let value = ctx.block_env().gas_limit;
+    ctx.stack_mut().push(value).map_err(InstructionError::StackError)?;
+    Ok(Control::Advance(1))
+}
+
+fn handleChainid(ctx: &mut ExecutionContext) -> Result<Control, InstructionError> {
+    let value = ctx.block_env().chain_id;
+    ctx.stack_mut().push(value).map_err(InstructionError::StackError)?;
+    Ok(Control::Advance(1))
+}
+
+fn handleSelfbalance(ctx: &mut ExecutionContext) -> Result<Control, InstructionError> {
+    let address = *ctx.contract_address();
+    let balance = ctx.state().balance(&address);
+    ctx.stack_mut().push(balance).map_err(InstructionError::StackError)?;
+    Ok(Control::Advance(1))
+}
+
+fn handleBasefee(ctx: &mut ExecutionContext) -> Result<Control, InstructionError> {
+    let value = ctx.block_env().base_fee;
+    ctx.stack_mut().push(value).map_err(InstructionError::StackError)?;
+    Ok(Control::Advance(1))
+}
+
+// Logging
+// LOGn pops the data region followed by n topics
+fn handleLog(ctx: &mut ExecutionContext, n: usize) -> Result<Control, InstructionError> {
+    ensure_not_static(ctx)?;
+    let offset = ctx.stack_mut().pop().map_err(InstructionError::StackError)?;
+    let length = ctx.stack_mut().pop().map_err(InstructionError::StackError)?;
+    let mut topics = Vec::with_capacity(n);
+    for _ in 0..n {
+        topics.push(ctx.stack_mut().pop().map_err(InstructionError::StackError)?);
+    }
+    
+    let data = if length.is_zero() {
+        Vec::new()
+    } else {
+        let offset = to_usize(offset)?;
+        let length = to_usize(length)?;
+        ctx.expand_memory(offset, length)?;
+        ctx.memory().load_range(offset, length).map_err(|_| InstructionError::OutOfBounds)?
+    };
+    
+    let log = Log { address: *ctx.contract_address(), topics, data };
+    ctx.logs_mut().push(log);
+    Ok(Control::Advance(1))
 }
 
 // Return
-fn handleReturn(ctx: &mut ExecutionContext) -> Result<(), InstructionError> {
+fn handleReturn(ctx: &mut ExecutionContext) -> Result<Control, InstructionError> {
+    let offset = ctx.stack_mut().pop().map_err(InstructionError::StackError)?;
+    let length = ctx.stack_mut().pop().map_err(InstructionError::StackError)?;
+    
+    // Empty return data may name any offset without touching memory
+    let (offset, length) = if length.is_zero() { (0, 0) } else { (to_usize(offset)?, to_usize(length)?) };
+    ctx.expand_memory(offset, length)?;
+    ctx.set_return_data(offset, length)
+        .map_err(|_| InstructionError::OutOfBounds)?;
+    ctx.set_status(ExecStatus::Returned);
+    Ok(Control::Stopped)
+}
+
+fn handleRevert(ctx: &mut ExecutionContext) -> Result<Control, InstructionError> {
     let offset = ctx.stack_mut().pop().map_err(InstructionError::StackError)?;
     let length = ctx.stack_mut().pop().map_err(InstructionError::StackError)?;
     
-    ctx.set_return_data(offset.as_usize(), length.as_usize())
-        .map_err(|_| InstructionError::InvalidOpcode)?;
-    ctx.set_pc(ctx.pc() + 1);
+    // Empty return data may name any offset without touching memory
+    let (offset, length) = if length.is_zero() { (0, 0) } else { (to_usize(offset)?, to_usize(length)?) };
+    ctx.expand_memory(offset, length)?;
+    ctx.set_return_data(offset, length)
+        .map_err(|_| InstructionError::OutOfBounds)?;
+    ctx.set_status(ExecStatus::Reverted);
+    Err(InstructionError::Revert)
+}
+
+// System Operations
+// Gas forwarded to a child frame: what was asked for, capped at all but
+// one 64th of what the caller has left (EIP-150)
+fn child_gas_limit(ctx: &ExecutionContext, requested: U256) -> u64 {
+    let remaining = ctx.gas().remaining();
+    let available = remaining - remaining / 64;
+    if requested < U256::from(available) { requested.as_u64() } else { available }
+}
+
+// Swap the state shared by every frame of a transaction between two contexts
+fn swap_shared_state(a: &mut ExecutionContext, b: &mut ExecutionContext) {
+    core::mem::swap(a.transient_storage_mut(), b.transient_storage_mut());
+    core::mem::swap(a.state_mut(), b.state_mut());
+    core::mem::swap(a.destroyed_mut(), b.destroyed_mut());
+    core::mem::swap(a.access_list_mut(), b.access_list_mut());
+    core::mem::swap(a.original_storage_mut(), b.original_storage_mut());
+}
+
+// Stack space that must be left before running a child frame, and the size
+// of each new segment allocated when there is less
+#[cfg(feature = "std")]
+const CHILD_STACK_RED_ZONE: usize = 256 * 1024;
+#[cfg(feature = "std")]
+const CHILD_STACK_SIZE: usize = 4 * 1024 * 1024;
+
+// Each nested frame recurses through the interpreter, so a chain of calls up
+// to the depth limit can need more stack than the thread has; grow it on
+// demand instead of overflowing
+#[cfg(feature = "std")]
+fn run_nested(child: &mut ExecutionContext) -> Result<(), ExecutionError> {
+    stacker::maybe_grow(CHILD_STACK_RED_ZONE, CHILD_STACK_SIZE, || run(child))
+}
+
+// Without std the stack cannot be grown, so the embedder has to provide one
+// deep enough for the calls it expects
+#[cfg(not(feature = "std"))]
+fn run_nested(child: &mut ExecutionContext) -> Result<(), ExecutionError> {
+    run(child)
+}
+
+// Move value from the parent's account to the child's, run the prepared
+// child frame on the parent's shared state, charge the parent for the gas
+// it used and make its output the parent's return data. The stipend is gas
+// the child was given on top of what the parent set aside for it.
+// Returns whether the child completed without reverting or failing
+fn run_child(ctx: &mut ExecutionContext, child: &mut ExecutionContext, value: U256, stipend: u64) -> Result<bool, InstructionError> {
+    // The child's own storage is rolled back by the interpreter; everything
+    // else it may touch, including the transfer, is restored here
+    let state = ctx.state().clone();
+    let destroyed = ctx.destroyed().clone();
+    let access_list = ctx.access_list().clone();
+    
+    if !value.is_zero() {
+        let from = *ctx.contract_address();
+        let to = *child.contract_address();
+        ctx.state_mut().transfer(from, to, value);
+    }
+    
+    swap_shared_state(ctx, child);
+    let result = run_nested(child);
+    swap_shared_state(ctx, child);
+    
+    // (success, gas used, whether the child's output is kept)
+    let (success, gas_used, keep_output) = match &result {
+        Ok(()) => (true, child.gas().used(), true),
+        Err(err) if matches!(err.kind, InstructionError::Revert) => (false, child.gas().used(), true),
+        // Exceptional halts forfeit all of the child's gas
+        Err(_) => (false, child.gas().limit(), false),
+    };
+    charge_child_gas(ctx, gas_used, stipend)?;
+    
+    if success {
+        let logs = core::mem::take(child.logs_mut());
+        ctx.logs_mut().extend(logs);
+        ctx.add_gas_refund(child.gas_refund());
+    } else {
+        *ctx.state_mut() = state;
+        *ctx.destroyed_mut() = destroyed;
+        *ctx.access_list_mut() = access_list;
+    }
+    *ctx.return_data_mut() = if keep_output { core::mem::take(child.return_data_mut()) } else { Vec::new() };
+    Ok(success)
+}
+
+// Charge the parent for gas a child used. The stipend was never the
+// parent's, so only gas beyond it is charged, and whatever of it the child
+// left unused goes to the parent
+fn charge_child_gas(ctx: &mut ExecutionContext, gas_used: u64, stipend: u64) -> Result<(), InstructionError> {
+    ctx.gas_mut().charge(gas_used.saturating_sub(stipend))?;
+    let unused = stipend.saturating_sub(gas_used);
+    let used = ctx.gas().used();
+    ctx.gas_mut().set_used(used.saturating_sub(unused));
+    Ok(())
+}
+
+// Stand in for a child frame when the callee is a precompile, making its
+// output the return data. A precompile that runs out of gas fails like any
+// exceptional halt, using up everything it was given
+fn call_precompile(ctx: &mut ExecutionContext, output: Vec<u8>, cost: u64, gas_limit: u64, stipend: u64) -> Result<bool, InstructionError> {
+    let success = cost <= gas_limit;
+    if success {
+        charge_child_gas(ctx, cost, stipend)?;
+        *ctx.return_data_mut() = output;
+    } else {
+        charge_child_gas(ctx, gas_limit, stipend)?;
+        ctx.return_data_mut().clear();
+    }
+    Ok(success)
+}
+
+// Read a memory region for call input, charging for any expansion
+fn read_memory_region(ctx: &mut ExecutionContext, offset: U256, length: U256) -> Result<Vec<u8>, InstructionError> {
+    if length.is_zero() {
+        return Ok(Vec::new());
+    }
+    let offset = to_usize(offset)?;
+    let length = to_usize(length)?;
+    ctx.expand_memory(offset, length)?;
+    ctx.memory().load_range(offset, length).map_err(|_| InstructionError::OutOfBounds)
+}
+
+// Copy as much of the return data as fits into the caller's output region
+fn write_call_output(ctx: &mut ExecutionContext, offset: usize, length: usize) -> Result<(), InstructionError> {
+    let size = length.min(ctx.return_data().len());
+    if size > 0 {
+        let output = ctx.return_data()[..size].to_vec();
+        ctx.memory_mut().store_range(offset, &output).map_err(|_| InstructionError::OutOfBounds)?;
+    }
+    Ok(())
+}
+
+// Address of a contract created by `sender` at `nonce`:
+// keccak256(rlp([sender, nonce]))[12..]
+pub fn create_address(sender: &Address, nonce: u64) -> Address {
+    let hash = keccak256(&rlp_encode_address_nonce(sender, nonce));
+    let mut address = [0u8; 20];
+    address.copy_from_slice(&hash[12..]);
+    address
+}
+
+// Run init code from memory and deploy whatever it returns at an address
+// derived from this contract's nonce
+fn handleCreate(ctx: &mut ExecutionContext) -> Result<Control, InstructionError> {
+    ensure_not_static(ctx)?;
+    let value = ctx.stack_mut().pop().map_err(InstructionError::StackError)?;
+    let offset = ctx.stack_mut().pop().map_err(InstructionError::StackError)?;
+    let length = ctx.stack_mut().pop().map_err(InstructionError::StackError)?;
+    let init_code = read_memory_region(ctx, offset, length)?;
+    
+    let self_address = *ctx.contract_address();
+    let balance = ctx.state().balance(&self_address);
+    ctx.return_data_mut().clear();
+    if ctx.depth() >= CALL_DEPTH_LIMIT || balance < value {
+        ctx.stack_mut().push(U256::zero()).map_err(InstructionError::StackError)?;
+        return Ok(Control::Advance(1));
+    }
+    
+    // The creator's nonce goes up whether or not the creation succeeds
+    let nonce = ctx.state().nonce(&self_address);
+    ctx.state_mut().set_nonce(self_address, nonce + 1);
+    let address = create_address(&self_address, nonce);
+    ctx.access_list_mut().warm_address(address);
+    
+    let occupied = !ctx.state().code(&address).is_empty() || ctx.state().nonce(&address) != 0;
+    if occupied {
+        // A collision burns the gas that would have been forwarded
+        let forfeited = child_gas_limit(ctx, U256::MAX);
+        ctx.gas_mut().charge(forfeited)?;
+        ctx.stack_mut().push(U256::zero()).map_err(InstructionError::StackError)?;
+        return Ok(Control::Advance(1));
+    }
+    
+    let mut child = ExecutionContext::new(address, init_code, Vec::new());
+    *child.tx_env_mut() = ctx.tx_env().clone();
+    child.tx_env_mut().caller = self_address;
+    child.tx_env_mut().value = value;
+    *child.block_env_mut() = ctx.block_env().clone();
+    child.set_hardfork(ctx.hardfork());
+    *child.stack_mut() = Stack::with_max_depth(ctx.stack().max_depth());
+    *child.gas_mut() = Gas::new(child_gas_limit(ctx, U256::MAX));
+    child.set_depth(ctx.depth() + 1);
+    
+    let own_storage = core::mem::take(ctx.storage_mut());
+    ctx.state_mut().set_storage(self_address, own_storage);
+    *child.storage_mut() = ctx.state_mut().take_storage(&address);
+    
+    // New contracts start at nonce 1 (EIP-161)
+    ctx.state_mut().set_nonce(address, 1);
+    let success = run_child(ctx, &mut child, value, 0);
+    
+    let child_storage = core::mem::take(child.storage_mut());
+    ctx.state_mut().set_storage(address, child_storage);
+    *ctx.storage_mut() = ctx.state_mut().take_storage(&self_address);
+    let success = success?;
+    
+    let result = if success {
+        // The returned bytes become the contract's code rather than return data
+        let code = core::mem::take(ctx.return_data_mut());
+        ctx.state_mut().set_code(address, code);
+        address_to_word(&address)
+    } else {
+        ctx.state_mut().set_nonce(address, 0);
+        U256::zero()
+    };
+    ctx.stack_mut().push(result).map_err(InstructionError::StackError)?;
+    Ok(Control::Advance(1))
+}
+
+// Fail if the current frame may not modify state
+fn ensure_not_static(ctx: &ExecutionContext) -> Result<(), InstructionError> {
+    if ctx.static_mode() {
+        return Err(InstructionError::StaticModeViolation);
+    }
     Ok(())
 }
+
+fn handleCall(ctx: &mut ExecutionContext) -> Result<Control, InstructionError> {
+    let gas = ctx.stack_mut().pop().map_err(InstructionError::StackError)?;
+    let address = word_to_address(ctx.stack_mut().pop().map_err(InstructionError::StackError)?);
+    access_account(ctx, address)?;
+    let value = ctx.stack_mut().pop().map_err(InstructionError::StackError)?;
+    // Sending value is a state change
+    if !value.is_zero() {
+        ensure_not_static(ctx)?;
+    }
+    let static_mode = ctx.static_mode();
+    call_account(ctx, gas, address, value, static_mode)
+}
+
+// Like CALL without value, but the callee and anything it calls may not
+// modify state
+fn handleStaticcall(ctx: &mut ExecutionContext) -> Result<Control, InstructionError> {
+    let gas = ctx.stack_mut().pop().map_err(InstructionError::StackError)?;
+    let address = word_to_address(ctx.stack_mut().pop().map_err(InstructionError::StackError)?);
+    access_account(ctx, address)?;
+    call_account(ctx, gas, address, U256::zero(), true)
+}
+
+// Shared tail of CALL and STATICCALL: pops the argument and return regions
+// and runs the target's code in its own frame
+fn call_account(ctx: &mut ExecutionContext, gas: U256, address: Address, value: U256, static_mode: bool) -> Result<Control, InstructionError> {
+    let args_offset = ctx.stack_mut().pop().map_err(InstructionError::StackError)?;
+    let args_length = ctx.stack_mut().pop().map_err(InstructionError::StackError)?;
+    let ret_offset = ctx.stack_mut().pop().map_err(InstructionError::StackError)?;
+    let ret_length = ctx.stack_mut().pop().map_err(InstructionError::StackError)?;
+    
+    let input = read_memory_region(ctx, args_offset, args_length)?;
+    let (ret_offset, ret_length) = if ret_length.is_zero() { (0, 0) } else { (to_usize(ret_offset)?, to_usize(ret_length)?) };
+    ctx.expand_memory(ret_offset, ret_length)?;
+    
+    // Sending value costs extra, and the callee gets a stipend on top of the
+    // gas forwarded so it can at least log the payment
+    let stipend = if value.is_zero() {
+        0
+    } else {
+        ctx.gas_mut().charge(CALL_VALUE_COST)?;
+        CALL_STIPEND
+    };
+    
+    let self_address = *ctx.contract_address();
+    let balance = ctx.state().balance(&self_address);
+    if ctx.depth() >= CALL_DEPTH_LIMIT || balance < value {
+        ctx.return_data_mut().clear();
+        ctx.stack_mut().push(U256::zero()).map_err(InstructionError::StackError)?;
+        return Ok(Control::Advance(1));
+    }
+    
+    let gas_limit = child_gas_limit(ctx, gas) + stipend;
+    if let Some((output, cost)) = run_precompile(&address, &input, gas_limit) {
+        let success = call_precompile(ctx, output, cost, gas_limit, stipend)?;
+        if success && !value.is_zero() {
+            ctx.state_mut().transfer(self_address, address, value);
+        }
+        write_call_output(ctx, ret_offset, ret_length)?;
+        let flag = if success { U256::from(1) } else { U256::zero() };
+        ctx.stack_mut().push(flag).map_err(InstructionError::StackError)?;
+        return Ok(Control::Advance(1));
+    }
+    
+    let code = ctx.state().code(&address).to_vec();
+    let mut child = ExecutionContext::new(address, code, input);
+    *child.tx_env_mut() = ctx.tx_env().clone();
+    child.tx_env_mut().caller = self_address;
+    child.tx_env_mut().value = value;
+    *child.block_env_mut() = ctx.block_env().clone();
+    child.set_hardfork(ctx.hardfork());
+    *child.stack_mut() = Stack::with_max_depth(ctx.stack().max_depth());
+    *child.gas_mut() = Gas::new(gas_limit);
+    child.set_depth(ctx.depth() + 1);
+    child.set_static_mode(static_mode);
+    
+    // Park this contract's storage with the other accounts so the callee
+    // sees it too if it calls back in, then hand the callee its own
+    let own_storage = core::mem::take(ctx.storage_mut());
+    ctx.state_mut().set_storage(self_address, own_storage);
+    *child.storage_mut() = ctx.state_mut().take_storage(&address);
+    
+    let success = run_child(ctx, &mut child, value, stipend);
+    
+    let child_storage = core::mem::take(child.storage_mut());
+    ctx.state_mut().set_storage(address, child_storage);
+    *ctx.storage_mut() = ctx.state_mut().take_storage(&self_address);
+    let success = success?;
+    
+    write_call_output(ctx, ret_offset, ret_length)?;
+    let flag = if success { U256::from(1) } else { U256::zero() };
+    ctx.stack_mut().push(flag).map_err(InstructionError::StackError)?;
+    Ok(Control::Advance(1))
+}
+
+// Run another account's code against this contract's storage, address,
+// caller and value
+fn handleDelegatecall(ctx: &mut ExecutionContext) -> Result<Control, InstructionError> {
+    let gas = ctx.stack_mut().pop().map_err(InstructionError::StackError)?;
+    let address = word_to_address(ctx.stack_mut().pop().map_err(InstructionError::StackError)?);
+    access_account(ctx, address)?;
+    let args_offset = ctx.stack_mut().pop().map_err(InstructionError::StackError)?;
+    let args_length = ctx.stack_mut().pop().map_err(InstructionError::StackError)?;
+    let ret_offset = ctx.stack_mut().pop().map_err(InstructionError::StackError)?;
+    let ret_length = ctx.stack_mut().pop().map_err(InstructionError::StackError)?;
+    
+    let input = read_memory_region(ctx, args_offset, args_length)?;
+    let (ret_offset, ret_length) = if ret_length.is_zero() { (0, 0) } else { (to_usize(ret_offset)?, to_usize(ret_length)?) };
+    ctx.expand_memory(ret_offset, ret_length)?;
+    
+    if ctx.depth() >= CALL_DEPTH_LIMIT {
+        ctx.return_data_mut().clear();
+        ctx.stack_mut().push(U256::zero()).map_err(InstructionError::StackError)?;
+        return Ok(Control::Advance(1));
+    }
+    
+    let gas_limit = child_gas_limit(ctx, gas);
+    if let Some((output, cost)) = run_precompile(&address, &input, gas_limit) {
+        let success = call_precompile(ctx, output, cost, gas_limit, 0)?;
+        write_call_output(ctx, ret_offset, ret_length)?;
+        let flag = if success { U256::from(1) } else { U256::zero() };
+        ctx.stack_mut().push(flag).map_err(InstructionError::StackError)?;
+        return Ok(Control::Advance(1));
+    }
+    
+    let code = ctx.state().code(&address).to_vec();
+    let mut child = ExecutionContext::new(*ctx.contract_address(), code, input);
+    *child.tx_env_mut() = ctx.tx_env().clone();
+    *child.block_env_mut() = ctx.block_env().clone();
+    child.set_hardfork(ctx.hardfork());
+    *child.stack_mut() = Stack::with_max_depth(ctx.stack().max_depth());
+    *child.gas_mut() = Gas::new(gas_limit);
+    child.set_depth(ctx.depth() + 1);
+    child.set_static_mode(ctx.static_mode());
+    
+    // The callee works directly on this contract's storage
+    core::mem::swap(ctx.storage_mut(), child.storage_mut());
+    let success = run_child(ctx, &mut child, U256::zero(), 0);
+    core::mem::swap(ctx.storage_mut(), child.storage_mut());
+    let success = success?;
+    
+    write_call_output(ctx, ret_offset, ret_length)?;
+    let flag = if success { U256::from(1) } else { U256::zero() };
+    ctx.stack_mut().push(flag).map_err(InstructionError::StackError)?;
+    Ok(Control::Advance(1))
+}
+
+// Send this contract's whole balance to the beneficiary and schedule the
+// contract for removal at the end of execution
+fn handleSelfdestruct(ctx: &mut ExecutionContext) -> Result<Control, InstructionError> {
+    ensure_not_static(ctx)?;
+    let beneficiary = word_to_address(ctx.stack_mut().pop().map_err(InstructionError::StackError)?);
+    // SELFDESTRUCT has no warm cost in its base price, so a cold
+    // beneficiary pays the full access cost
+    if ctx.hardfork().has_access_lists() && !ctx.access_list_mut().warm_address(beneficiary) {
+        ctx.gas_mut().charge(COLD_ACCOUNT_ACCESS_COST)?;
+    }
+    let address = *ctx.contract_address();
+    
+    // Zeroing first means a contract naming itself as beneficiary gets its
+    // balance back, only to have it burned along with the account
+    let balance = ctx.state().balance(&address);
+    ctx.state_mut().set_balance(address, U256::zero());
+    let account = ctx.state_mut().get_or_create(beneficiary);
+    account.balance = account.balance.saturating_add(balance);
+    ctx.destroyed_mut().insert(address);
+    ctx.stop();
+    Ok(Control::Stopped)
+}
+
+// Unlike an undefined opcode, INVALID is an explicit abort that burns all gas
+fn handleInvalid(ctx: &mut ExecutionContext) -> Result<Control, InstructionError> {
+    ctx.gas_mut().consume_all();
+    ctx.stop();
+    Err(InstructionError::InvalidInstruction)
+}
+
+#[cfg(test)]
+mod tests;
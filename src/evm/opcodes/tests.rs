@@ -0,0 +1,910 @@
+use super::*;
+use crate::evm::interpreter::run;
+use crate::evm::state::WorldState;
+use crate::evm::calldata::CalldataError;
+
+// Two's complement of x
+fn neg(x: u64) -> U256 {
+    (!U256::from(x)).overflowing_add(U256::one()).0
+}
+
+// Execute a single opcode on a stack built from items, the last of them on
+// top, and return what it leaves on top
+fn exec(opcode: u8, items: &[U256]) -> U256 {
+    let mut ctx = ExecutionContext::new([0u8; 20], vec![opcode], vec![]);
+    for item in items {
+        ctx.stack_mut().push(*item).unwrap();
+    }
+    execute_opcode(opcode, &mut ctx).unwrap();
+    ctx.stack().peek(0).unwrap()
+}
+
+// Run code in a fresh context that must not fail
+fn run_code(code: Vec<u8>) -> ExecutionContext {
+    let mut ctx = ExecutionContext::new([0u8; 20], code, vec![]);
+    run(&mut ctx).unwrap();
+    ctx
+}
+
+#[test]
+fn sdiv_signs() {
+    // Operands are pushed divisor first, so the dividend is on top
+    assert_eq!(exec(SDIV, &[neg(2), neg(8)]), U256::from(4));
+    assert_eq!(exec(SDIV, &[U256::from(2), neg(7)]), neg(3));
+    assert_eq!(exec(SDIV, &[neg(2), U256::from(7)]), neg(3));
+    // MIN / -1 overflows back to MIN
+    let min = U256::one() << 255;
+    assert_eq!(exec(SDIV, &[neg(1), min]), min);
+    assert_eq!(exec(SDIV, &[U256::zero(), neg(8)]), U256::zero());
+}
+
+#[test]
+fn smod_takes_the_sign_of_the_dividend() {
+    assert_eq!(exec(SMOD, &[U256::from(3), neg(8)]), neg(2));
+    assert_eq!(exec(SMOD, &[neg(3), U256::from(8)]), U256::from(2));
+    assert_eq!(exec(SMOD, &[U256::zero(), U256::from(8)]), U256::zero());
+}
+
+#[test]
+fn addmod_and_mulmod_do_not_wrap_the_intermediate() {
+    // Operands are pushed modulus first; MAX + 2 and MAX * MAX need more
+    // than 256 bits before the modulus is taken
+    assert_eq!(exec(ADDMOD, &[U256::from(3), U256::from(2), U256::MAX]), U256::from(2));
+    assert_eq!(exec(ADDMOD, &[U256::from(2), U256::from(2), U256::MAX]), U256::one());
+    assert_eq!(exec(MULMOD, &[U256::from(7), U256::MAX, U256::MAX]), U256::one());
+    assert_eq!(exec(MULMOD, &[U256::from(12), U256::MAX, U256::MAX]), U256::from(9));
+}
+
+#[test]
+fn addmod_and_mulmod_by_zero_are_zero() {
+    assert_eq!(exec(ADDMOD, &[U256::zero(), U256::from(2), U256::from(5)]), U256::zero());
+    assert_eq!(exec(MULMOD, &[U256::zero(), U256::from(2), U256::from(5)]), U256::zero());
+}
+
+#[test]
+fn signed_comparisons() {
+    // -1 < 0 once the sign bit is taken into account
+    assert_eq!(exec(SLT, &[U256::zero(), neg(1)]), U256::one());
+    assert_eq!(exec(SGT, &[U256::zero(), neg(1)]), U256::zero());
+    assert_eq!(exec(SLT, &[neg(1), neg(2)]), U256::one());
+    assert_eq!(exec(SGT, &[U256::from(3), U256::from(5)]), U256::one());
+}
+
+#[test]
+fn byte_indexes_from_the_most_significant_end() {
+    let x = U256::from(0xab) | (U256::from(0xcd) << 248);
+    assert_eq!(exec(BYTE, &[x, U256::from(31)]), U256::from(0xab));
+    assert_eq!(exec(BYTE, &[x, U256::zero()]), U256::from(0xcd));
+    assert_eq!(exec(BYTE, &[x, U256::from(32)]), U256::zero());
+    assert_eq!(exec(BYTE, &[x, U256::MAX]), U256::zero());
+}
+
+#[test]
+fn shifts_at_the_edges() {
+    let v = U256::from(0xff);
+    assert_eq!(exec(SHL, &[v, U256::zero()]), v);
+    assert_eq!(exec(SHL, &[U256::one(), U256::from(255)]), U256::one() << 255);
+    assert_eq!(exec(SHL, &[v, U256::from(256)]), U256::zero());
+    assert_eq!(exec(SHR, &[v, U256::zero()]), v);
+    assert_eq!(exec(SHR, &[U256::one() << 255, U256::from(255)]), U256::one());
+    assert_eq!(exec(SHR, &[v, U256::from(256)]), U256::zero());
+    // SAR fills with the sign bit
+    assert_eq!(exec(SAR, &[neg(16), U256::zero()]), neg(16));
+    assert_eq!(exec(SAR, &[neg(16), U256::from(2)]), neg(4));
+    assert_eq!(exec(SAR, &[U256::one() << 255, U256::from(255)]), U256::MAX);
+    assert_eq!(exec(SAR, &[neg(16), U256::from(256)]), U256::MAX);
+    assert_eq!(exec(SAR, &[U256::from(16), U256::from(256)]), U256::zero());
+}
+
+#[test]
+fn sha3_hashes_memory() {
+    let hash = |hex| U256::from_str_radix(hex, 16).unwrap();
+    let empty = hash("c5d2460186f7233c927e7db2dcc703c0e500b653ca82273b7bfad8045d85a470");
+    assert_eq!(exec(SHA3, &[U256::zero(), U256::zero()]), empty);
+
+    let mut ctx = ExecutionContext::new([0u8; 20], vec![SHA3], vec![]);
+    ctx.memory_mut().store_bytes(0, b"abc").unwrap();
+    ctx.stack_mut().push(U256::from(3)).unwrap();
+    ctx.stack_mut().push(U256::zero()).unwrap();
+    execute_opcode(SHA3, &mut ctx).unwrap();
+    let abc = hash("4e03657aea45a94fc7d47ba826c8d667c0d1e6e33a64a036ec44f58fa12d6c45");
+    assert_eq!(ctx.stack().peek(0).unwrap(), abc);
+}
+
+#[test]
+fn sload_reads_what_sstore_wrote() {
+    let ctx = run_code(vec![PUSH1, 42, PUSH1, 7, SSTORE, PUSH1, 7, SLOAD, PUSH1, 8, SLOAD]);
+    assert_eq!(ctx.stack().peek(1).unwrap(), U256::from(42));
+    assert_eq!(ctx.stack().peek(0).unwrap(), U256::zero());
+}
+
+// A context whose stack holds 1..=n, n on top
+fn counted_stack(n: u64) -> ExecutionContext {
+    let mut ctx = ExecutionContext::new([0u8; 20], vec![], vec![]);
+    for i in 1..=n {
+        ctx.stack_mut().push(U256::from(i)).unwrap();
+    }
+    ctx
+}
+
+#[test]
+fn dup16_copies_the_sixteenth_item() {
+    let mut ctx = counted_stack(16);
+    execute_opcode(DUP16, &mut ctx).unwrap();
+    assert_eq!(ctx.stack().len(), 17);
+    assert_eq!(ctx.stack().peek(0).unwrap(), U256::one());
+
+    let mut ctx = counted_stack(15);
+    let err = execute_opcode(DUP16, &mut ctx).unwrap_err();
+    assert!(matches!(err, InstructionError::StackError(StackError::StackUnderflow)));
+}
+
+#[test]
+fn swap16_exchanges_the_top_and_seventeenth_items() {
+    let mut ctx = counted_stack(17);
+    execute_opcode(SWAP16, &mut ctx).unwrap();
+    assert_eq!(ctx.stack().peek(0).unwrap(), U256::one());
+    assert_eq!(ctx.stack().peek(16).unwrap(), U256::from(17));
+
+    let mut ctx = counted_stack(16);
+    let err = execute_opcode(SWAP16, &mut ctx).unwrap_err();
+    assert!(matches!(err, InstructionError::StackError(StackError::StackUnderflow)));
+}
+
+#[test]
+fn push0_and_push20() {
+    let mut code = vec![PUSH0, PUSH20];
+    code.extend(1..=20u8);
+    let mut ctx = ExecutionContext::new([0u8; 20], code, vec![]);
+    assert_eq!(execute_opcode(PUSH0, &mut ctx).unwrap(), Control::Advance(1));
+    ctx.set_pc(1);
+    assert_eq!(execute_opcode(PUSH20, &mut ctx).unwrap(), Control::Advance(21));
+    let mut word = [0u8; 32];
+    for (i, byte) in word[12..].iter_mut().enumerate() {
+        *byte = i as u8 + 1;
+    }
+    assert_eq!(ctx.stack().peek(0).unwrap(), U256::from_big_endian(&word));
+    assert_eq!(ctx.stack().peek(1).unwrap(), U256::zero());
+}
+
+#[test]
+fn msize_rounds_a_single_byte_up_to_a_word() {
+    let ctx = run_code(vec![PUSH1, 1, PUSH1, 0, MSTORE8, MSIZE]);
+    assert_eq!(ctx.stack().peek(0).unwrap(), U256::from(32));
+}
+
+#[test]
+fn transaction_opcodes_read_the_tx_env() {
+    let mut ctx = ExecutionContext::new([0u8; 20], vec![CALLER, CALLVALUE, ORIGIN, GASPRICE], vec![]);
+    ctx.tx_env_mut().caller = [0x11; 20];
+    ctx.tx_env_mut().origin = [0x22; 20];
+    ctx.tx_env_mut().value = U256::from(1000);
+    ctx.tx_env_mut().gas_price = U256::from(7);
+    run(&mut ctx).unwrap();
+    assert_eq!(ctx.stack().peek(0).unwrap(), U256::from(7));
+    assert_eq!(ctx.stack().peek(1).unwrap(), U256::from_big_endian(&[0x22; 20]));
+    assert_eq!(ctx.stack().peek(2).unwrap(), U256::from(1000));
+    assert_eq!(ctx.stack().peek(3).unwrap(), U256::from_big_endian(&[0x11; 20]));
+}
+
+#[test]
+fn block_opcodes_read_the_block_env() {
+    let code = vec![COINBASE, TIMESTAMP, NUMBER, PREVRANDAO, GASLIMIT];
+    let mut ctx = ExecutionContext::new([0u8; 20], code, vec![]);
+    ctx.block_env_mut().coinbase = [0x33; 20];
+    ctx.block_env_mut().timestamp = U256::from(1);
+    ctx.block_env_mut().number = U256::from(2);
+    ctx.block_env_mut().prev_randao = U256::from(3);
+    ctx.block_env_mut().gas_limit = U256::from(4);
+    run(&mut ctx).unwrap();
+    for depth in 0..4 {
+        assert_eq!(ctx.stack().peek(depth).unwrap(), U256::from(4 - depth));
+    }
+    assert_eq!(ctx.stack().peek(4).unwrap(), U256::from_big_endian(&[0x33; 20]));
+}
+
+#[test]
+fn blockhash_only_sees_the_last_256_blocks() {
+    let mut ctx = ExecutionContext::new([0u8; 20], vec![], vec![]);
+    ctx.block_env_mut().number = U256::from(1000);
+    for number in [999u64, 744, 743, 1000, 1001] {
+        ctx.block_env_mut().block_hashes.insert(number, U256::from(number));
+    }
+    // The current block and anything outside the window read as zero
+    for (number, expected) in [(999u64, 999u64), (744, 744), (743, 0), (1000, 0), (1001, 0)] {
+        ctx.stack_mut().push(U256::from(number)).unwrap();
+        execute_opcode(BLOCKHASH, &mut ctx).unwrap();
+        assert_eq!(ctx.stack_mut().pop().unwrap(), U256::from(expected));
+    }
+    ctx.stack_mut().push(U256::MAX).unwrap();
+    execute_opcode(BLOCKHASH, &mut ctx).unwrap();
+    assert_eq!(ctx.stack_mut().pop().unwrap(), U256::zero());
+}
+
+#[test]
+fn log2_records_topics_and_data() {
+    // Log the byte 0xab with topics 0x11 and 0x22
+    let code = vec![
+        PUSH1, 0xab, PUSH1, 0, MSTORE8,
+        PUSH1, 0x22, PUSH1, 0x11, PUSH1, 1, PUSH1, 0, LOG2,
+    ];
+    let mut ctx = ExecutionContext::new([9u8; 20], code, vec![]);
+    run(&mut ctx).unwrap();
+    assert_eq!(ctx.logs().len(), 1);
+    let log = &ctx.logs()[0];
+    assert_eq!(log.address, [9u8; 20]);
+    assert_eq!(log.topics, [U256::from(0x11), U256::from(0x22)]);
+    assert_eq!(log.data, [0xab]);
+}
+
+#[test]
+fn revert_returns_its_data_and_fails_the_run() {
+    // Revert with the 4-byte Error(string) selector
+    let code = vec![
+        PUSH4, 0x08, 0xc3, 0x79, 0xa0, PUSH1, 0xe0, SHL, PUSH1, 0, MSTORE,
+        PUSH1, 4, PUSH1, 0, REVERT,
+    ];
+    let mut ctx = ExecutionContext::new([0u8; 20], code, vec![]);
+    let err = run(&mut ctx).unwrap_err();
+    assert!(matches!(err.kind, InstructionError::Revert));
+    assert_eq!(ctx.status(), ExecStatus::Reverted);
+    assert_eq!(ctx.return_data().as_slice(), [0x08, 0xc3, 0x79, 0xa0]);
+}
+
+#[test]
+fn gas_used_is_the_sum_of_static_costs() {
+    let ctx = run_code(vec![PUSH1, 2, PUSH1, 3, MUL, JUMPDEST, POP, STOP]);
+    assert_eq!(ctx.gas().used(), 3 + 3 + 5 + 1 + 2);
+
+    let mut ctx = ExecutionContext::builder().code(vec![PUSH1, 2, PUSH1, 3, MUL]).gas_limit(10).build();
+    let err = run(&mut ctx).unwrap_err();
+    assert!(matches!(err.kind, InstructionError::OutOfGas));
+    assert_eq!(err.opcode, MUL);
+}
+
+#[test]
+fn memory_expansion_is_quadratic() {
+    // MSTORE at 0x10000 ends at 0x10020, 2049 words
+    let ctx = run_code(vec![PUSH1, 1, PUSH3, 0x01, 0x00, 0x00, MSTORE]);
+    let words = 2049;
+    assert_eq!(ctx.gas().used(), 3 + 3 + 3 + 3 * words + words * words / 512);
+    assert_eq!(ctx.memory().size(), 2049 * 32);
+
+    // Out of gas is reported before memory grows
+    let mut ctx = ExecutionContext::new([0u8; 20], vec![PUSH1, 1, PUSH5, 0x01, 0, 0, 0, 0, MSTORE], vec![]);
+    let err = run(&mut ctx).unwrap_err();
+    assert!(matches!(err.kind, InstructionError::OutOfGas));
+    assert_eq!(ctx.memory().size(), 0);
+}
+
+#[test]
+fn gas_pushes_what_is_left_after_itself() {
+    let mut ctx = ExecutionContext::builder().code(vec![PUSH1, 2, PUSH1, 3, ADD, GAS]).gas_limit(100).build();
+    run(&mut ctx).unwrap();
+    assert_eq!(ctx.stack().peek(0).unwrap(), U256::from(100 - 9 - 2));
+}
+
+#[test]
+fn invalid_consumes_all_gas() {
+    let mut ctx = ExecutionContext::builder().code(vec![PUSH1, 2, INVALID]).gas_limit(1000).build();
+    let err = run(&mut ctx).unwrap_err();
+    assert!(matches!(err.kind, InstructionError::InvalidInstruction));
+    assert_eq!(ctx.gas().remaining(), 0);
+    assert_eq!(ctx.gas().used(), 1000);
+}
+
+#[test]
+fn errors_display_readable_messages() {
+    let cases = [
+        (InstructionError::InvalidOpcode, "invalid opcode"),
+        (InstructionError::StackError(StackError::StackOverflow), "stack overflow"),
+        (InstructionError::StackError(StackError::StackUnderflow), "stack underflow"),
+        (InstructionError::StackError(StackError::IndexError), "stack index out of range"),
+        (InstructionError::StackError(StackError::InvalidStackItem), "invalid stack item"),
+        (InstructionError::MemoryError(MemoryError::MemoryOverflow), "memory overflow"),
+        (InstructionError::MemoryError(MemoryError::MemoryAccessError), "memory access out of bounds"),
+        (InstructionError::StorageError(StorageError::StorageAccessError), "storage access error"),
+        (InstructionError::InvalidJump, "invalid jump destination"),
+        (InstructionError::Revert, "execution reverted"),
+        (InstructionError::OutOfGas, "out of gas"),
+        (InstructionError::InvalidInstruction, "invalid instruction"),
+        (InstructionError::OutOfBounds, "offset or length out of bounds"),
+        (InstructionError::StaticModeViolation, "state modification in static call"),
+        (InstructionError::StepLimitExceeded, "step limit exceeded"),
+    ];
+    for (err, message) in cases {
+        assert_eq!(err.to_string(), message);
+    }
+    assert_eq!(CalldataError::InvalidCalldataAccess.to_string(), "invalid calldata access");
+    // Wrapped errors are exposed as the source
+    let err = InstructionError::StackError(StackError::StackUnderflow);
+    assert!(core::error::Error::source(&err).is_some());
+    assert!(core::error::Error::source(&InstructionError::OutOfGas).is_none());
+}
+
+// code with a PUSH32 of U256::MAX spliced between before and after
+fn around_max(before: &[u8], after: &[u8]) -> Vec<u8> {
+    let mut code = before.to_vec();
+    code.push(PUSH32);
+    code.extend([0xff; 32]);
+    code.extend(after);
+    code
+}
+
+#[test]
+fn max_offsets_fail_cleanly() {
+    let mut ctx = ExecutionContext::new([0u8; 20], around_max(&[], &[MLOAD]), vec![]);
+    assert!(matches!(run(&mut ctx).unwrap_err().kind, InstructionError::OutOfBounds));
+
+    let mut ctx = ExecutionContext::new([0u8; 20], around_max(&[PUSH1, 1], &[MSTORE]), vec![]);
+    assert!(matches!(run(&mut ctx).unwrap_err().kind, InstructionError::OutOfBounds));
+
+    let mut ctx = ExecutionContext::new([0u8; 20], around_max(&[], &[JUMP]), vec![]);
+    assert!(matches!(run(&mut ctx).unwrap_err().kind, InstructionError::InvalidJump));
+
+    // An empty range never touches memory, wherever it starts
+    run_code(around_max(&[PUSH1, 0], &[RETURN]));
+
+    let mut ctx = ExecutionContext::new([0u8; 20], around_max(&[PUSH1, 1], &[REVERT]), vec![]);
+    assert!(matches!(run(&mut ctx).unwrap_err().kind, InstructionError::OutOfBounds));
+
+    // The largest offset that fits in a usize, so the end is what overflows
+    let mut code = vec![PUSH1, 1, PUSH8];
+    code.extend([0xff; 8]);
+    code.push(RETURN);
+    let mut ctx = ExecutionContext::new([0u8; 20], code, vec![]);
+    assert!(matches!(run(&mut ctx).unwrap_err().kind, InstructionError::OutOfBounds));
+}
+
+#[test]
+fn push_data_is_not_a_jumpdest() {
+    let mut code = vec![PUSH1, 4, JUMP, PUSH32, 0x5b];
+    code.extend([0u8; 31]);
+    assert!(!valid_jumpdests(&code).contains(&4));
+    let mut ctx = ExecutionContext::new([0u8; 20], code, vec![]);
+    assert!(matches!(run(&mut ctx).unwrap_err().kind, InstructionError::InvalidJump));
+
+    let ctx = run_code(vec![PUSH1, 4, JUMP, STOP, JUMPDEST, PUSH1, 1]);
+    assert_eq!(ctx.stack().peek(0).unwrap(), U256::one());
+}
+
+#[test]
+fn extcodecopy_charges_per_word() {
+    // Copy 3200 bytes of this contract's own code, which is already warm
+    let ctx = run_code(vec![PUSH2, 0x0c, 0x80, PUSH1, 0, PUSH1, 0, ADDRESS, EXTCODECOPY]);
+    let memory = 100 * 3 + 100 * 100 / 512;
+    assert_eq!(ctx.gas().used(), 3 + 3 + 3 + 2 + 100 + memory + copy_cost(3200));
+    assert_eq!(ctx.gas().used(), 730);
+}
+
+#[test]
+fn balance_and_selfbalance() {
+    let mut funded = [0u8; 20];
+    funded[19] = 0xaa;
+    let me = [7u8; 20];
+    let code = vec![PUSH1, 0xaa, BALANCE, PUSH1, 0xbb, BALANCE, SELFBALANCE];
+    let mut ctx = ExecutionContext::new(me, code, vec![]);
+    ctx.state_mut().set_balance(funded, U256::from(500));
+    ctx.state_mut().set_balance(me, U256::from(9));
+    run(&mut ctx).unwrap();
+    assert_eq!(ctx.stack().peek(0).unwrap(), U256::from(9));
+    assert_eq!(ctx.stack().peek(1).unwrap(), U256::zero());
+    assert_eq!(ctx.stack().peek(2).unwrap(), U256::from(500));
+}
+
+#[test]
+fn chainid_and_basefee() {
+    let mut ctx = ExecutionContext::new([0u8; 20], vec![CHAINID, BASEFEE], vec![]);
+    // Mainnet unless told otherwise
+    assert_eq!(ctx.block_env().chain_id, U256::one());
+    ctx.block_env_mut().chain_id = U256::from(10);
+    ctx.block_env_mut().base_fee = U256::from(77);
+    run(&mut ctx).unwrap();
+    assert_eq!(ctx.stack().peek(0).unwrap(), U256::from(77));
+    assert_eq!(ctx.stack().peek(1).unwrap(), U256::from(10));
+}
+
+#[test]
+fn transient_storage_is_separate_and_starts_empty() {
+    let code = vec![PUSH1, 9, PUSH1, 1, TSTORE, PUSH1, 1, TLOAD, PUSH1, 1, SLOAD];
+    let ctx = run_code(code.clone());
+    assert_eq!(ctx.stack().peek(1).unwrap(), U256::from(9));
+    assert_eq!(ctx.stack().peek(0).unwrap(), U256::zero());
+    assert!(ctx.transient_storage().contains(&[0u8; 20], U256::one()));
+
+    let fresh = ExecutionContext::new([0u8; 20], code, vec![]);
+    assert!(!fresh.transient_storage().contains(&[0u8; 20], U256::one()));
+}
+
+// A contract at [7; 20] that sets transient slot 0 to 42 and then calls
+// [9; 20] with the given opcode. The callee stores one more than what it
+// reads from transient slot 0 in slot 0, then sets transient slot 0 to 7
+fn call_after_tstore(opcode: u8) -> ExecutionContext {
+    let callee = [9u8; 20];
+    let mut state = WorldState::new();
+    state.set_code(callee, vec![PUSH1, 0, TLOAD, PUSH1, 1, ADD, PUSH1, 0, SSTORE, PUSH1, 7, PUSH1, 0, TSTORE, STOP]);
+    let mut code = vec![PUSH1, 42, PUSH1, 0, TSTORE, PUSH1, 0, PUSH1, 0, PUSH1, 0, PUSH1, 0];
+    if opcode == CALL {
+        code.extend([PUSH1, 0]);
+    }
+    code.push(PUSH20);
+    code.extend(callee);
+    code.extend([GAS, opcode, STOP]);
+    let mut ctx = ExecutionContext::builder().address([7u8; 20]).code(code).state(state).build();
+    run(&mut ctx).unwrap();
+    assert_eq!(ctx.stack().peek(0).unwrap(), U256::one());
+    ctx
+}
+
+#[test]
+fn called_contract_has_its_own_transient_storage() {
+    let ctx = call_after_tstore(CALL);
+    let callee_storage = &ctx.state().account(&[9u8; 20]).unwrap().storage;
+    assert_eq!(callee_storage.load(U256::zero()).unwrap(), U256::one());
+    assert_eq!(ctx.transient_storage().load(&[7u8; 20], U256::zero()).unwrap(), U256::from(42));
+    assert_eq!(ctx.transient_storage().load(&[9u8; 20], U256::zero()).unwrap(), U256::from(7));
+}
+
+#[test]
+fn delegatecall_shares_the_callers_transient_storage() {
+    let ctx = call_after_tstore(DELEGATECALL);
+    assert_eq!(ctx.storage().load(U256::zero()).unwrap(), U256::from(43));
+    assert_eq!(ctx.transient_storage().load(&[7u8; 20], U256::zero()).unwrap(), U256::from(7));
+    assert!(!ctx.transient_storage().contains(&[9u8; 20], U256::zero()));
+}
+
+#[test]
+fn delegatecall_runs_precompiles() {
+    // DELEGATECALL the identity precompile with the single byte 0xaa,
+    // writing its output to memory at 32
+    let ctx = run_code(vec![
+        PUSH1, 0xaa, PUSH1, 0, MSTORE8,
+        PUSH1, 1, PUSH1, 32, PUSH1, 1, PUSH1, 0, PUSH1, 4, GAS, DELEGATECALL, STOP,
+    ]);
+    assert_eq!(ctx.stack().peek(0).unwrap(), U256::one());
+    assert_eq!(ctx.return_data(), &vec![0xaa]);
+    assert_eq!(ctx.memory().load_range(32, 1).unwrap(), vec![0xaa]);
+}
+
+#[test]
+fn delegatecall_shares_the_callers_storage() {
+    let library = [9u8; 20];
+    let mut state = WorldState::new();
+    // Store 0x2a in slot 1 and ADDRESS in slot 2
+    state.set_code(library, vec![PUSH1, 0x2a, PUSH1, 1, SSTORE, ADDRESS, PUSH1, 2, SSTORE, STOP]);
+    let mut code = vec![PUSH1, 0, PUSH1, 0, PUSH1, 0, PUSH1, 0, PUSH20];
+    code.extend(library);
+    code.extend([GAS, DELEGATECALL, STOP]);
+    let mut ctx = ExecutionContext::builder().address([7u8; 20]).code(code).state(state).build();
+    run(&mut ctx).unwrap();
+    
+    assert_eq!(ctx.stack().peek(0).unwrap(), U256::one());
+    assert_eq!(ctx.storage().load(U256::one()).unwrap(), U256::from(0x2a));
+    assert_eq!(ctx.storage().load(U256::from(2)).unwrap(), address_to_word(&[7u8; 20]));
+    assert!(ctx.state().account(&library).is_none_or(|account| account.storage.iter().next().is_none()));
+}
+
+// A contract at [7; 20] holding 100 wei that CALLs [9; 20] with no gas of
+// its own and the given value
+fn call_with_value(callee_code: Vec<u8>, value: u8) -> ExecutionContext {
+    let (caller, callee) = ([7u8; 20], [9u8; 20]);
+    let mut state = WorldState::new();
+    state.set_balance(caller, U256::from(100));
+    state.set_code(callee, callee_code);
+    let mut code = vec![PUSH1, 0, PUSH1, 0, PUSH1, 0, PUSH1, 0, PUSH1, value, PUSH20];
+    code.extend(callee);
+    code.extend([PUSH1, 0, CALL, STOP]);
+    let mut ctx = ExecutionContext::builder().address(caller).code(code).state(state).build();
+    run(&mut ctx).unwrap();
+    ctx
+}
+
+#[test]
+fn value_call_pays_for_the_transfer_and_funds_a_stipend() {
+    // The callee logs, which it can only afford with the stipend
+    let ctx = call_with_value(vec![PUSH1, 0, PUSH1, 0, LOG0, STOP], 5);
+    assert_eq!(ctx.stack().peek(0).unwrap(), U256::one());
+    assert_eq!(ctx.state().balance(&[9u8; 20]), U256::from(5));
+    assert_eq!(ctx.logs().len(), 1);
+    // Pushes, CALL with a cold callee and the value cost; the 381 the callee
+    // used came out of the stipend, and the rest of it goes to the caller
+    assert_eq!(ctx.gas().used(), 21 + 100 + 2500 + CALL_VALUE_COST + 381 - CALL_STIPEND);
+    
+    // Without value there is no stipend, so the same callee runs out of gas
+    let ctx = call_with_value(vec![PUSH1, 0, PUSH1, 0, LOG0, STOP], 0);
+    assert_eq!(ctx.stack().peek(0).unwrap(), U256::zero());
+    assert!(ctx.logs().is_empty());
+}
+
+#[test]
+fn reverted_value_call_keeps_the_value() {
+    let ctx = call_with_value(vec![PUSH1, 0, PUSH1, 0, REVERT], 5);
+    assert_eq!(ctx.stack().peek(0).unwrap(), U256::zero());
+    assert_eq!(ctx.state().balance(&[7u8; 20]), U256::from(100));
+    assert_eq!(ctx.state().balance(&[9u8; 20]), U256::zero());
+    assert_eq!(ctx.gas().used(), 21 + 100 + 2500 + CALL_VALUE_COST + 6 - CALL_STIPEND);
+}
+
+// A context that STATICCALLs a contract running callee_code and holding
+// balance wei, with a 32-byte output buffer
+fn staticcall_with_balance(callee_code: Vec<u8>, balance: u64) -> ExecutionContext {
+    let (caller, callee) = ([0xaa; 20], [0xbb; 20]);
+    let mut code = vec![PUSH1, 32, PUSH0, PUSH0, PUSH0, PUSH20];
+    code.extend(callee);
+    code.extend([PUSH2, 0xff, 0xff, STATICCALL]);
+    let mut ctx = ExecutionContext::new(caller, code, vec![]);
+    ctx.state_mut().set_code(callee, callee_code);
+    ctx.state_mut().set_balance(callee, U256::from(balance));
+    ctx
+}
+
+fn staticcall(callee_code: Vec<u8>) -> ExecutionContext {
+    let mut ctx = staticcall_with_balance(callee_code, 0);
+    run(&mut ctx).unwrap();
+    ctx
+}
+
+#[test]
+fn staticcall_forbids_state_changes() {
+    let ctx = staticcall(vec![PUSH1, 1, PUSH0, SSTORE]);
+    assert_eq!(ctx.stack().peek(0).unwrap(), U256::zero());
+    let storage = ctx.state().account(&[0xbb; 20]).map(|account| &account.storage);
+    assert!(storage.is_none_or(|storage| !storage.contains(U256::zero())));
+
+    // A view call returns its output
+    let ctx = staticcall(vec![PUSH1, 9, PUSH0, MSTORE, PUSH1, 32, PUSH0, RETURN]);
+    assert_eq!(ctx.stack().peek(0).unwrap(), U256::one());
+    assert_eq!(ctx.memory().load(0).unwrap(), U256::from(9));
+}
+
+#[test]
+fn create_deploys_the_returned_code() {
+    let sender = [0x11; 20];
+    // The init code returns the single byte 0xfe as the runtime code
+    let init = [PUSH1, 0xfe, PUSH0, MSTORE8, PUSH1, 1, PUSH0, RETURN];
+    let mut code = vec![PUSH8];
+    code.extend(init);
+    code.extend([PUSH0, MSTORE, PUSH1, 8, PUSH1, 24, PUSH0, CREATE]);
+    let mut ctx = ExecutionContext::new(sender, code, vec![]);
+    run(&mut ctx).unwrap();
+
+    let address = create_address(&sender, 0);
+    assert_eq!(ctx.stack().peek(0).unwrap(), U256::from_big_endian(&address));
+    assert_eq!(ctx.state().code(&address), [0xfe]);
+    assert_eq!(ctx.state().nonce(&sender), 1);
+    assert_eq!(ctx.state().nonce(&address), 1);
+}
+
+#[test]
+fn selfdestruct_transfers_and_deletes() {
+    let (me, target, beneficiary) = ([0xaa; 20], [0xbb; 20], [0xcc; 20]);
+    let mut destruct = vec![PUSH20];
+    destruct.extend(beneficiary);
+    destruct.push(SELFDESTRUCT);
+
+    // A called contract destroys itself
+    let mut code = vec![PUSH0, PUSH0, PUSH0, PUSH0, PUSH0, PUSH20];
+    code.extend(target);
+    code.extend([PUSH2, 0xff, 0xff, CALL]);
+    let mut ctx = ExecutionContext::new(me, code, vec![]);
+    ctx.state_mut().set_code(target, destruct.clone());
+    ctx.state_mut().set_balance(target, U256::from(9));
+    run(&mut ctx).unwrap();
+    assert_eq!(ctx.stack().peek(0).unwrap(), U256::one());
+    assert!(ctx.destroyed().contains(&target));
+    assert!(!ctx.state().exists(&target));
+    assert_eq!(ctx.state().balance(&beneficiary), U256::from(9));
+
+    // The running contract destroys itself
+    let mut ctx = ExecutionContext::new(me, destruct.clone(), vec![]);
+    ctx.state_mut().set_balance(me, U256::from(3));
+    ctx.storage_mut().store(U256::one(), U256::one()).unwrap();
+    run(&mut ctx).unwrap();
+    assert_eq!(ctx.state().balance(&beneficiary), U256::from(3));
+    assert!(!ctx.storage().contains(U256::one()));
+
+    // Not allowed in a static call
+    let mut ctx = staticcall_with_balance(destruct, 9);
+    run(&mut ctx).unwrap();
+    assert_eq!(ctx.stack().peek(0).unwrap(), U256::zero());
+    assert!(ctx.destroyed().is_empty());
+    assert_eq!(ctx.state().balance(&[0xbb; 20]), U256::from(9));
+}
+
+#[test]
+fn truncated_push_is_padded_and_flagged() {
+    // PUSH2 with only one byte of immediate left
+    let ctx = run_code(vec![PUSH1, 1, PUSH2, 0xff]);
+    assert_eq!(ctx.stack().peek(0).unwrap(), U256::from(0xff00));
+    assert!(ctx.truncated_push());
+    assert_eq!(ctx.status(), ExecStatus::Stopped);
+    
+    let ctx = run_code(vec![PUSH2, 0xff, 0x01]);
+    assert_eq!(ctx.stack().peek(0).unwrap(), U256::from(0xff01));
+    assert!(!ctx.truncated_push());
+}
+
+#[test]
+fn second_sload_of_a_slot_is_warm() {
+    let ctx = run_code(vec![PUSH1, 1, SLOAD, GAS, PUSH1, 1, SLOAD, GAS]);
+    let after_first = ctx.stack().peek(2).unwrap().as_u64();
+    let after_second = ctx.stack().peek(0).unwrap().as_u64();
+    assert_eq!(ctx.gas().limit() - after_first, 3 + COLD_SLOAD_COST + 2);
+    assert_eq!(after_first - after_second, 3 + WARM_STORAGE_READ_COST + 2);
+
+    // The same holds for accounts; the running contract starts out warm
+    let ctx = run_code(vec![PUSH1, 0x42, BALANCE, PUSH1, 0x42, BALANCE, ADDRESS, BALANCE]);
+    assert_eq!(ctx.gas().used(), 3 + COLD_ACCOUNT_ACCESS_COST + 3 + 100 + 2 + 100);
+}
+
+#[test]
+fn sstore_prices_set_clear_and_reset() {
+    // Setting a zero slot: cold access plus the set cost
+    let ctx = run_code(vec![PUSH1, 1, PUSH0, SSTORE]);
+    assert_eq!(ctx.gas().used(), 3 + 2 + COLD_SLOAD_COST + 20000);
+    assert_eq!(ctx.gas_refund(), 0);
+
+    // Clearing a slot earns a refund
+    let mut ctx = ExecutionContext::new([0u8; 20], vec![PUSH0, PUSH0, SSTORE], vec![]);
+    ctx.storage_mut().store(U256::zero(), U256::from(5)).unwrap();
+    run(&mut ctx).unwrap();
+    assert_eq!(ctx.gas().used(), 2 + 2 + 5000);
+    assert_eq!(ctx.gas_refund(), 4800);
+
+    // Resetting a slot back to its original value refunds most of the set
+    let ctx = run_code(vec![PUSH1, 1, PUSH0, SSTORE, PUSH0, PUSH0, SSTORE]);
+    assert_eq!(ctx.gas().used(), 3 + 2 + COLD_SLOAD_COST + 20000 + 2 + 2 + WARM_STORAGE_READ_COST);
+    assert_eq!(ctx.gas_refund(), 20000 - WARM_STORAGE_READ_COST as i64);
+
+    // Clearing then restoring takes the clear refund back
+    let mut ctx = ExecutionContext::new([0u8; 20], vec![PUSH0, PUSH0, SSTORE, PUSH1, 5, PUSH0, SSTORE], vec![]);
+    ctx.storage_mut().store(U256::zero(), U256::from(5)).unwrap();
+    run(&mut ctx).unwrap();
+    assert_eq!(ctx.gas_refund(), 5000 - COLD_SLOAD_COST as i64 - WARM_STORAGE_READ_COST as i64);
+}
+
+#[test]
+fn codecopy_pads_past_the_end_of_code() {
+    // Copy 40 bytes of code from offset 2 into memory 0
+    let code = vec![PUSH1, 40, PUSH1, 2, PUSH0, CODECOPY];
+    let ctx = run_code(code.clone());
+    let mut expected = code[2..].to_vec();
+    expected.resize(40, 0);
+    assert_eq!(ctx.memory().load_range(0, 40).unwrap(), expected);
+    assert_eq!(ctx.gas().used(), 3 + 3 + 2 + 3 + copy_cost(40) + 2 * 3);
+
+    // A huge length runs out of gas instead of allocating
+    let mut ctx = ExecutionContext::new([0u8; 20], vec![PUSH8, 0x10, 0, 0, 0, 0, 0, 0, 0, PUSH0, PUSH0, CODECOPY], vec![]);
+    assert!(matches!(run(&mut ctx).unwrap_err().kind, InstructionError::OutOfGas));
+    assert_eq!(ctx.memory().size(), 0);
+}
+
+#[test]
+fn status_tells_how_the_frame_ended() {
+    let mut ctx = ExecutionContext::new([0u8; 20], vec![PUSH1, 1, STOP], vec![]);
+    assert_eq!(ctx.status(), ExecStatus::Running);
+    run(&mut ctx).unwrap();
+    assert_eq!(ctx.status(), ExecStatus::Stopped);
+
+    let ctx = run_code(vec![PUSH1, 1, PUSH0, RETURN]);
+    assert_eq!(ctx.status(), ExecStatus::Returned);
+
+    let mut ctx = ExecutionContext::new([0u8; 20], vec![PUSH0, PUSH0, REVERT], vec![]);
+    run(&mut ctx).unwrap_err();
+    assert_eq!(ctx.status(), ExecStatus::Reverted);
+
+    // Running off the end is an implicit STOP
+    let ctx = run_code(vec![PUSH0]);
+    assert_eq!(ctx.status(), ExecStatus::Stopped);
+}
+
+#[test]
+fn exp_charges_per_exponent_byte() {
+    let gas_for = |exponent: U256| {
+        let mut ctx = ExecutionContext::new([0u8; 20], vec![EXP], vec![]);
+        ctx.stack_mut().push(exponent).unwrap();
+        ctx.stack_mut().push(U256::from(3)).unwrap();
+        execute_opcode(EXP, &mut ctx).unwrap();
+        ctx.gas().used()
+    };
+    assert_eq!(gas_for(U256::zero()), 10);
+    assert_eq!(gas_for(U256::from(0xff)), 10 + 50);
+    assert_eq!(gas_for(U256::from(0x100)), 10 + 50 * 2);
+    assert_eq!(gas_for(U256::MAX), 10 + 50 * 32);
+}
+
+#[test]
+fn call_to_the_identity_precompile_echoes_its_input() {
+    // CALL 0x04 with the word 0x2a at memory 0, output to memory 32
+    let code = vec![
+        PUSH1, 0x2a, PUSH0, MSTORE,
+        PUSH1, 32, PUSH1, 32, PUSH1, 32, PUSH0, PUSH0, PUSH1, 4, PUSH2, 0xff, 0xff, CALL,
+    ];
+    let ctx = run_code(code);
+    assert_eq!(ctx.stack().peek(0).unwrap(), U256::one());
+    assert_eq!(ctx.memory().load(32).unwrap(), U256::from(0x2a));
+    assert_eq!(ctx.return_data().len(), 32);
+
+    // 17 gas does not cover the 18 the precompile costs for one word
+    let ctx = run_code(vec![PUSH1, 32, PUSH1, 32, PUSH1, 32, PUSH0, PUSH1, 4, PUSH1, 17, STATICCALL]);
+    assert_eq!(ctx.stack().peek(0).unwrap(), U256::zero());
+    assert!(ctx.return_data().is_empty());
+}
+
+#[test]
+fn stack_is_checked_before_anything_changes() {
+    let mut ctx = ExecutionContext::new([0u8; 20], vec![PUSH1, 7, ADD], vec![]);
+    let err = run(&mut ctx).unwrap_err();
+    assert!(matches!(err.kind, InstructionError::StackError(StackError::StackUnderflow)));
+    assert_eq!(err.pc, 2);
+    // The failing ADD did not pop the one item it could have
+    assert_eq!(ctx.stack().as_slice(), &[U256::from(7)]);
+
+    let mut ctx = ExecutionContext::new([0u8; 20], vec![PUSH0; 1025], vec![]);
+    let err = run(&mut ctx).unwrap_err();
+    assert!(matches!(err.kind, InstructionError::StackError(StackError::StackOverflow)));
+    assert_eq!(ctx.stack().len(), 1024);
+}
+
+// Memory after MCOPY of len bytes from src to dest, with 1..=32 stored at
+// offset 0 first, and the gas the run used
+fn mcopy(dest: u8, src: u8, len: u8) -> (Vec<u8>, u64) {
+    let mut code = vec![PUSH32];
+    code.extend(1..=32u8);
+    code.extend([PUSH0, MSTORE, PUSH1, len, PUSH1, src, PUSH1, dest, MCOPY]);
+    let ctx = run_code(code);
+    (ctx.memory().load_range(0, ctx.memory().size()).unwrap(), ctx.gas().used())
+}
+
+#[test]
+fn mcopy_handles_overlap_in_both_directions() {
+    let pattern: Vec<u8> = (1..=32).collect();
+    // Into fresh memory, which grows by a word
+    let (memory, gas) = mcopy(32, 0, 32);
+    assert_eq!(memory[..32], pattern[..]);
+    assert_eq!(memory[32..], pattern[..]);
+    assert_eq!(gas, 3 + 2 + 3 + 3 + 3 * 3 + 3 + copy_cost(32) + 3);
+
+    // Destination after the source
+    let (memory, _) = mcopy(4, 0, 8);
+    let mut expected = pattern.clone();
+    expected[4..12].copy_from_slice(&pattern[..8]);
+    assert_eq!(memory, expected);
+
+    // Destination before the source
+    let (memory, _) = mcopy(0, 4, 8);
+    let mut expected = pattern.clone();
+    expected[..8].copy_from_slice(&pattern[4..12]);
+    assert_eq!(memory, expected);
+}
+
+#[test]
+fn push_near_the_top_of_the_pc_range() {
+    let mut ctx = ExecutionContext::new([0u8; 20], vec![PUSH2, 1, 2], vec![]);
+    // The PC after the immediate would not fit in a usize
+    ctx.set_pc(usize::MAX - 1);
+    assert!(matches!(execute_opcode(PUSH2, &mut ctx), Err(InstructionError::OutOfBounds)));
+    assert!(ctx.stack().is_empty());
+    assert_eq!(ctx.pc(), usize::MAX - 1);
+
+    // Just fits, reading zeros past the end of the code
+    ctx.set_pc(usize::MAX - 3);
+    assert_eq!(execute_opcode(PUSH2, &mut ctx).unwrap(), Control::Advance(3));
+    assert_eq!(ctx.stack().peek(0).unwrap(), U256::zero());
+
+    // Past the end of code is an implicit STOP that leaves the PC alone
+    ctx.set_pc(usize::MAX);
+    crate::evm::interpreter::step(&mut ctx).unwrap();
+    assert_eq!(ctx.pc(), usize::MAX);
+    assert!(ctx.stopped());
+}
+
+#[test]
+fn handlers_say_where_the_pc_goes() {
+    let code = vec![PUSH1, 4, JUMP, INVALID, JUMPDEST, PUSH3, 1, 2, 3, STOP];
+    let mut ctx = ExecutionContext::new([0u8; 20], code, vec![]);
+    assert_eq!(execute_opcode(PUSH1, &mut ctx).unwrap(), Control::Advance(2));
+    // The handler leaves moving the PC to the run loop
+    assert_eq!(ctx.pc(), 0);
+    ctx.set_pc(2);
+    assert_eq!(execute_opcode(JUMP, &mut ctx).unwrap(), Control::Jumped);
+    assert_eq!(ctx.pc(), 4);
+    assert_eq!(execute_opcode(JUMPDEST, &mut ctx).unwrap(), Control::Advance(1));
+    ctx.set_pc(5);
+    assert_eq!(execute_opcode(PUSH3, &mut ctx).unwrap(), Control::Advance(4));
+    ctx.set_pc(9);
+    assert_eq!(execute_opcode(STOP, &mut ctx).unwrap(), Control::Stopped);
+    assert_eq!(ctx.pc(), 9);
+}
+
+#[test]
+fn extcodehash_tells_missing_accounts_from_empty_code() {
+    let target = [0x77; 20];
+    let hash_of = |state: WorldState| {
+        let mut code = vec![PUSH20];
+        code.extend(target);
+        code.push(EXTCODEHASH);
+        let mut ctx = ExecutionContext::builder().code(code).state(state).build();
+        run(&mut ctx).unwrap();
+        ctx.stack().peek(0).unwrap()
+    };
+    assert_eq!(hash_of(WorldState::new()), U256::zero());
+
+    let mut state = WorldState::new();
+    state.set_balance(target, U256::one());
+    assert_eq!(hash_of(state), U256::from_big_endian(&keccak256(&[])));
+
+    let mut state = WorldState::new();
+    state.set_code(target, vec![STOP]);
+    assert_eq!(hash_of(state), U256::from_big_endian(&keccak256(&[STOP])));
+}
+
+#[test]
+fn self_recursion_stops_at_the_call_depth_limit() {
+    // Count the frames in slot 0, then call this contract again
+    let code = vec![
+        PUSH1, 0, SLOAD, PUSH1, 1, ADD, PUSH1, 0, SSTORE,
+        PUSH1, 0, PUSH1, 0, PUSH1, 0, PUSH1, 0, PUSH1, 0, ADDRESS, GAS, CALL, STOP,
+    ];
+    let mut state = WorldState::new();
+    state.set_code([7u8; 20], code.clone());
+    let mut ctx = ExecutionContext::builder().address([7u8; 20]).code(code).state(state).build();
+    ctx.set_depth(CALL_DEPTH_LIMIT - 2);
+    run(&mut ctx).unwrap();
+    // Frames run at depths LIMIT - 2, LIMIT - 1 and LIMIT; the last one's call fails
+    assert_eq!(ctx.storage().load(U256::zero()).unwrap(), U256::from(3));
+    assert_eq!(ctx.stack().peek(0).unwrap(), U256::one());
+}
+
+#[test]
+fn calldatacopy_pads_past_the_end_of_calldata() {
+    // Copy 40 bytes from calldata offset 2 of 4
+    let code = vec![PUSH1, 40, PUSH1, 2, PUSH0, CALLDATACOPY];
+    let mut ctx = ExecutionContext::new([0u8; 20], code, vec![1, 2, 3, 4]);
+    run(&mut ctx).unwrap();
+    let mut expected = vec![0u8; 64];
+    expected[..2].copy_from_slice(&[3, 4]);
+    assert_eq!(ctx.memory().load_range(0, 64).unwrap(), expected);
+    assert_eq!(ctx.gas().used(), 3 + 3 + 2 + 3 + copy_cost(40) + 2 * 3);
+
+    // A source offset past usize still reads zeros
+    let mut ctx = ExecutionContext::new([0u8; 20], around_max(&[PUSH1, 32], &[PUSH0, CALLDATACOPY]), vec![1; 8]);
+    run(&mut ctx).unwrap();
+    assert_eq!(ctx.memory().load_range(0, 32).unwrap(), [0u8; 32]);
+
+    // A huge length runs out of gas, and one past usize is out of bounds
+    let mut code = vec![PUSH8];
+    code.extend([0xff; 8]);
+    code.extend([PUSH0, PUSH0, CALLDATACOPY]);
+    let mut ctx = ExecutionContext::new([0u8; 20], code, vec![1; 8]);
+    assert!(matches!(run(&mut ctx).unwrap_err().kind, InstructionError::OutOfGas));
+    let mut ctx = ExecutionContext::new([0u8; 20], around_max(&[], &[PUSH0, PUSH0, CALLDATACOPY]), vec![]);
+    assert!(matches!(run(&mut ctx).unwrap_err().kind, InstructionError::OutOfBounds));
+}
+
+#[test]
+fn sha3_charges_per_word_hashed() {
+    // Hashing 64 bytes of fresh memory: 30 + 6 per word, plus two words of expansion
+    let ctx = run_code(vec![PUSH1, 64, PUSH0, SHA3]);
+    assert_eq!(ctx.gas().used(), 3 + 2 + (30 + 6 * 2) + 2 * 3);
+    assert_eq!(ctx.stack().peek(0).unwrap(), U256::from_big_endian(&keccak256(&[0u8; 64])));
+
+    // Memory that is already expanded costs nothing more
+    let ctx = run_code(vec![PUSH0, PUSH1, 32, MSTORE, PUSH1, 64, PUSH0, SHA3]);
+    assert_eq!(ctx.gas().used(), 2 + 3 + 3 + 2 * 3 + 3 + 2 + (30 + 6 * 2));
+}
+
+#[test]
+fn calldataload_past_usize_reads_zero() {
+    let mut ctx = ExecutionContext::new([0u8; 20], around_max(&[], &[CALLDATALOAD]), vec![0xaa; 64]);
+    run(&mut ctx).unwrap();
+    assert_eq!(ctx.stack().peek(0).unwrap(), U256::zero());
+
+    // 2^64, which would truncate to offset 0
+    let mut code = vec![PUSH9, 1];
+    code.extend([0; 8]);
+    code.push(CALLDATALOAD);
+    let mut ctx = ExecutionContext::new([0u8; 20], code, vec![0xaa; 64]);
+    run(&mut ctx).unwrap();
+    assert_eq!(ctx.stack().peek(0).unwrap(), U256::zero());
+}
@@ -0,0 +1,215 @@
+use crate::evm::context::Address;
+use crate::evm::hash::keccak256;
+use crate::evm::opcodes::padded_slice;
+use k256::ecdsa::{RecoveryId, Signature, VerifyingKey};
+use num_bigint::BigUint;
+use primitive_types::U256;
+use alloc::{vec, vec::Vec};
+
+// Precompiled contracts live at the addresses 0x00..01 and up; these are the
+// low bytes of the ones implemented here
+pub const ECRECOVER: u8 = 0x01;
+pub const IDENTITY: u8 = 0x04;
+pub const MODEXP: u8 = 0x05;
+
+const PRECOMPILES: &[u8] = &[ECRECOVER, IDENTITY, MODEXP];
+
+fn precompile_id(address: &Address) -> Option<u8> {
+    if address[..19].iter().all(|&byte| byte == 0) && PRECOMPILES.contains(&address[19]) {
+        Some(address[19])
+    } else {
+        None
+    }
+}
+
+pub fn is_precompile(address: &Address) -> bool {
+    precompile_id(address).is_some()
+}
+
+// Run the precompile at `address` on `input`, returning its output and gas
+// cost, or None if no precompile lives there. Work that would cost more than
+// `gas_limit` may be skipped, since the call fails anyway
+pub fn run_precompile(address: &Address, input: &[u8], gas_limit: u64) -> Option<(Vec<u8>, u64)> {
+    let result = match precompile_id(address)? {
+        ECRECOVER => ecrecover(input),
+        IDENTITY => identity(input),
+        MODEXP => modexp(input, gas_limit),
+        _ => return None,
+    };
+    Some(result)
+}
+
+// Returns the input unchanged, for cheap memory copies
+fn identity(input: &[u8]) -> (Vec<u8>, u64) {
+    let cost = 15 + 3 * input.len().div_ceil(32) as u64;
+    (input.to_vec(), cost)
+}
+
+// Recover the address that signed a message hash. The input is the hash, v,
+// r and s as 32-byte words, zero-padded if short; the output is the address
+// as a word, or empty if the signature is invalid
+fn ecrecover(input: &[u8]) -> (Vec<u8>, u64) {
+    let mut data = [0u8; 128];
+    let size = input.len().min(data.len());
+    data[..size].copy_from_slice(&input[..size]);
+    
+    let output = match recover_address(&data) {
+        Some(address) => {
+            let mut word = vec![0u8; 32];
+            word[12..].copy_from_slice(&address);
+            word
+        }
+        None => Vec::new(),
+    };
+    (output, 3000)
+}
+
+// Signer of a hash, given the 128-byte precompile input (hash, v, r, s)
+pub(crate) fn recover_address(data: &[u8; 128]) -> Option<Address> {
+    let (hash, v, signature) = (&data[..32], &data[32..64], &data[64..]);
+    
+    // v is 27 or 28, written as a full word
+    if v[..31].iter().any(|&byte| byte != 0) || !matches!(v[31], 27 | 28) {
+        return None;
+    }
+    let recovery_id = RecoveryId::from_byte(v[31] - 27)?;
+    
+    // Rejects r or s that are zero or not below the curve order; on top of
+    // that, only the low-s form of a signature is accepted (EIP-2)
+    let signature = Signature::from_slice(signature).ok()?;
+    if signature.normalize_s().is_some() {
+        return None;
+    }
+    
+    let key = VerifyingKey::recover_from_prehash(hash, &signature, recovery_id).ok()?;
+    // The address is the tail of the hash of the uncompressed key, minus
+    // its 0x04 tag byte
+    let point = key.to_encoded_point(false);
+    let hash = keccak256(&point.as_bytes()[1..]);
+    let mut address = [0u8; 20];
+    address.copy_from_slice(&hash[12..]);
+    Some(address)
+}
+
+// base^exp % modulus over arbitrary-size integers (EIP-198). The input is
+// the three lengths as words followed by the numbers themselves, big-endian
+// and zero-padded if short; the output is padded to the modulus length
+fn modexp(input: &[u8], gas_limit: u64) -> (Vec<u8>, u64) {
+    let header = padded_slice(input, 0, 96);
+    let lengths = [&header[..32], &header[32..64], &header[64..]].map(|word| u64::try_from(U256::from_big_endian(word)));
+    let [Ok(base_len), Ok(exp_len), Ok(mod_len)] = lengths else {
+        // Lengths this large cost more gas than can ever be supplied
+        return (Vec::new(), u64::MAX);
+    };
+    
+    let exp_offset = 96usize.saturating_add(base_len as usize);
+    let exp_head = padded_slice(input, exp_offset, exp_len.min(32) as usize);
+    let cost = modexp_cost(base_len, exp_len, mod_len, &exp_head);
+    // Skip the work for calls that cannot pay for it or have nothing to
+    // return; the lengths are only known to be sane once the cost is paid
+    if cost > gas_limit || mod_len == 0 {
+        return (Vec::new(), cost);
+    }
+    
+    let (base_len, exp_len, mod_len) = (base_len as usize, exp_len as usize, mod_len as usize);
+    let base = BigUint::from_bytes_be(&padded_slice(input, 96, base_len));
+    let exponent = BigUint::from_bytes_be(&padded_slice(input, exp_offset, exp_len));
+    let modulus = BigUint::from_bytes_be(&padded_slice(input, exp_offset.saturating_add(exp_len), mod_len));
+    
+    let mut output = vec![0u8; mod_len];
+    // Anything mod zero is defined as zero
+    if modulus != BigUint::ZERO {
+        let result = base.modpow(&exponent, &modulus).to_bytes_be();
+        output[mod_len - result.len()..].copy_from_slice(&result);
+    }
+    (output, cost)
+}
+
+// EIP-2565 pricing: the square of the operand size in 64-bit words, times
+// roughly the number of squarings the exponent needs, over 3, and at least
+// 200. Saturates at u64::MAX
+fn modexp_cost(base_len: u64, exp_len: u64, mod_len: u64, exp_head: &[u8]) -> u64 {
+    let words = base_len.max(mod_len).div_ceil(8) as u128;
+    let complexity = words * words;
+    
+    // Bit length of the exponent less one, where only its first 32 bytes
+    // are looked at and each byte past them counts as 8 bits
+    let head_bits = U256::from_big_endian(exp_head).bits().saturating_sub(1) as u128;
+    let iterations = if exp_len <= 32 { head_bits } else { 8 * (exp_len as u128 - 32) + head_bits };
+    
+    let cost = complexity.saturating_mul(iterations.max(1)) / 3;
+    u64::try_from(cost.max(200)).unwrap_or(u64::MAX)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::format;
+    use alloc::string::String;
+
+    fn precompile(id: u8) -> Address {
+        let mut address = [0u8; 20];
+        address[19] = id;
+        address
+    }
+
+    // A big-endian word holding value, as hex
+    fn word(value: u64) -> String {
+        format!("{:064x}", value)
+    }
+
+    #[test]
+    fn ecrecover_recovers_the_signer() {
+        let hash = "18c547e4f7b0f325ad1e56f57e26c745b09a3e503d86e00e5255ff7f715d3d1c";
+        let r = "73b1693892219d736caba55bdb67216e485557ea6b6af75f37096c9aa6a5a75f";
+        let s = "1146bf4e2fc4de1c94f1b81868960f68bc842490f1b6bc98387ba6b57599fbf8";
+        let input = hex::decode(format!("{}{}{}{}", hash, word(27), r, s)).unwrap();
+        let (output, cost) = run_precompile(&precompile(ECRECOVER), &input, u64::MAX).unwrap();
+        assert_eq!(cost, 3000);
+        assert_eq!(hex::encode(output), format!("{:0>64}", "a94f5374fce5edbc8e2a8697c15331677e6ebf0b"));
+
+        // A bad v returns nothing rather than failing
+        let input = hex::decode(format!("{}{}{}{}", hash, word(29), r, s)).unwrap();
+        let (output, _) = run_precompile(&precompile(ECRECOVER), &input, u64::MAX).unwrap();
+        assert!(output.is_empty());
+    }
+
+    #[test]
+    fn ecrecover_rejects_out_of_range_signatures() {
+        let hash = "18c547e4f7b0f325ad1e56f57e26c745b09a3e503d86e00e5255ff7f715d3d1c";
+        let r = "73b1693892219d736caba55bdb67216e485557ea6b6af75f37096c9aa6a5a75f";
+        let s = "1146bf4e2fc4de1c94f1b81868960f68bc842490f1b6bc98387ba6b57599fbf8";
+        // The order of the secp256k1 group
+        let n = "fffffffffffffffffffffffffffffffebaaedce6af48a03bbfd25e8cd0364141";
+        // n - s, which signs the same hash with v = 28
+        let high_s = "eeb940b1d03b21e36b0e47e79769f095fe2ab855bd91e3a38756b7d75a9c4549";
+        let cases = [
+            (27, word(0), s.into()),
+            (27, r.into(), String::from(n)),
+            (27, r.into(), "ff".repeat(32)),
+            (28, r.into(), high_s.into()),
+        ];
+        for (v, r, s) in cases {
+            let input = hex::decode(format!("{}{}{}{}", hash, word(v), r, s)).unwrap();
+            let (output, cost) = run_precompile(&precompile(ECRECOVER), &input, u64::MAX).unwrap();
+            assert!(output.is_empty(), "r = {}, s = {}", r, s);
+            assert_eq!(cost, 3000);
+        }
+    }
+
+    #[test]
+    fn modexp_of_a_power_of_two() {
+        // 2^256 mod (2^256 - 1) = 1
+        let input = format!("{}{}{}020100{}", word(1), word(2), word(32), "ff".repeat(32));
+        let input = hex::decode(input).unwrap();
+        let (output, cost) = run_precompile(&precompile(MODEXP), &input, u64::MAX).unwrap();
+        assert_eq!(hex::encode(output), word(1));
+        assert_eq!(cost, 200);
+    }
+
+    #[test]
+    fn modexp_with_an_empty_modulus_returns_nothing() {
+        let input = hex::decode(format!("{}{}{}0302", word(1), word(1), word(0))).unwrap();
+        assert_eq!(run_precompile(&precompile(MODEXP), &input, u64::MAX).unwrap(), (vec![], 200));
+    }
+}
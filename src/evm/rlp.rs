@@ -0,0 +1,180 @@
+use crate::evm::context::Address;
+use alloc::{vec, vec::Vec};
+use core::fmt;
+use primitive_types::U256;
+
+// An RLP item, borrowing its bytes from the decoded input
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum RlpItem<'a> {
+    Bytes(&'a [u8]),
+    List(Vec<RlpItem<'a>>),
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum RlpError {
+    // The input ends before the item it announces
+    UnexpectedEnd,
+    // A valid item followed by more bytes
+    TrailingBytes,
+    // An item not written in its shortest form, which RLP forbids
+    NonCanonical,
+}
+
+impl fmt::Display for RlpError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RlpError::UnexpectedEnd => write!(f, "rlp input ends early"),
+            RlpError::TrailingBytes => write!(f, "rlp input has trailing bytes"),
+            RlpError::NonCanonical => write!(f, "rlp item is not canonically encoded"),
+        }
+    }
+}
+
+impl core::error::Error for RlpError {}
+
+// RLP of the list [address, nonce], the preimage of a CREATE address
+pub fn rlp_encode_address_nonce(address: &Address, nonce: u64) -> Vec<u8> {
+    rlp_encode_list(&[rlp_encode_bytes(address), encode_u64(nonce)])
+}
+
+pub fn rlp_encode_bytes(bytes: &[u8]) -> Vec<u8> {
+    // Single bytes below 0x80 are their own encoding
+    if let [byte @ 0..=0x7f] = bytes {
+        return vec![*byte];
+    }
+    let mut encoded = encode_length(bytes.len(), 0x80);
+    encoded.extend_from_slice(bytes);
+    encoded
+}
+
+// Wrap items that are already encoded into a list
+pub fn rlp_encode_list(items: &[Vec<u8>]) -> Vec<u8> {
+    let payload: Vec<u8> = items.concat();
+    let mut encoded = encode_length(payload.len(), 0xc0);
+    encoded.extend_from_slice(&payload);
+    encoded
+}
+
+// Integers are encoded as their big-endian bytes without leading zeros, so
+// zero is the empty string (0x80), not a single zero byte
+pub fn rlp_encode_u256(value: U256) -> Vec<u8> {
+    let bytes = value.to_big_endian();
+    let start = bytes.iter().position(|&b| b != 0).unwrap_or(bytes.len());
+    rlp_encode_bytes(&bytes[start..])
+}
+
+fn encode_u64(value: u64) -> Vec<u8> {
+    rlp_encode_u256(U256::from(value))
+}
+
+// The prefix of a string (offset 0x80) or list (0xc0) of `len` bytes; past
+// 55 bytes the length itself follows as big-endian bytes
+fn encode_length(len: usize, offset: u8) -> Vec<u8> {
+    if len <= 55 {
+        return vec![offset + len as u8];
+    }
+    let bytes = len.to_be_bytes();
+    let start = bytes.iter().position(|&b| b != 0).unwrap_or(bytes.len());
+    let mut encoded = vec![offset + 55 + (bytes.len() - start) as u8];
+    encoded.extend_from_slice(&bytes[start..]);
+    encoded
+}
+
+// Decode an input that holds exactly one item
+pub fn rlp_decode(input: &[u8]) -> Result<RlpItem<'_>, RlpError> {
+    let (item, rest) = decode_item(input)?;
+    if !rest.is_empty() {
+        return Err(RlpError::TrailingBytes);
+    }
+    Ok(item)
+}
+
+// Split the first item off the input, returning it and what follows
+fn decode_item(input: &[u8]) -> Result<(RlpItem<'_>, &[u8]), RlpError> {
+    let (&prefix, rest) = input.split_first().ok_or(RlpError::UnexpectedEnd)?;
+    let (is_list, len, rest) = match prefix {
+        0x00..=0x7f => return Ok((RlpItem::Bytes(&input[..1]), rest)),
+        0x80..=0xb7 => (false, (prefix - 0x80) as usize, rest),
+        0xb8..=0xbf => {
+            let (len, rest) = decode_long_length(rest, (prefix - 0xb7) as usize)?;
+            (false, len, rest)
+        }
+        0xc0..=0xf7 => (true, (prefix - 0xc0) as usize, rest),
+        0xf8..=0xff => {
+            let (len, rest) = decode_long_length(rest, (prefix - 0xf7) as usize)?;
+            (true, len, rest)
+        }
+    };
+    if rest.len() < len {
+        return Err(RlpError::UnexpectedEnd);
+    }
+    let (payload, rest) = rest.split_at(len);
+    
+    if !is_list {
+        // A lone byte below 0x80 must not carry a prefix
+        if let [0..=0x7f] = payload {
+            return Err(RlpError::NonCanonical);
+        }
+        return Ok((RlpItem::Bytes(payload), rest));
+    }
+    let mut items = Vec::new();
+    let mut remaining = payload;
+    while !remaining.is_empty() {
+        let (item, next) = decode_item(remaining)?;
+        items.push(item);
+        remaining = next;
+    }
+    Ok((RlpItem::List(items), rest))
+}
+
+// Read a length written as `size` big-endian bytes; the long form is only
+// allowed for lengths that do not fit the short one
+fn decode_long_length(input: &[u8], size: usize) -> Result<(usize, &[u8]), RlpError> {
+    if input.len() < size {
+        return Err(RlpError::UnexpectedEnd);
+    }
+    let (bytes, rest) = input.split_at(size);
+    if bytes[0] == 0 || size > size_of::<usize>() {
+        return Err(RlpError::NonCanonical);
+    }
+    let len = bytes.iter().fold(0usize, |len, &byte| (len << 8) | byte as usize);
+    if len <= 55 {
+        return Err(RlpError::NonCanonical);
+    }
+    Ok((len, rest))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::evm::opcodes::create_address;
+
+    const SENDER: Address = [
+        0x6a, 0xc7, 0xea, 0x33, 0xf8, 0x83, 0x1e, 0xa9, 0xdc, 0xc5,
+        0x33, 0x93, 0xaa, 0xa8, 0x8b, 0x25, 0xa7, 0x85, 0xdb, 0xf0,
+    ];
+
+    #[test]
+    fn nonce_zero_is_the_empty_string() {
+        let rlp = rlp_encode_address_nonce(&SENDER, 0);
+        assert_eq!(rlp.len(), 23);
+        assert_eq!((rlp[0], rlp[1], rlp[22]), (0xd6, 0x94, 0x80));
+        // 0x7f is the largest nonce that encodes as itself
+        let rlp = rlp_encode_address_nonce(&SENDER, 0x7f);
+        assert_eq!((rlp[0], rlp.len(), rlp[22]), (0xd6, 23, 0x7f));
+        let rlp = rlp_encode_address_nonce(&SENDER, 0x80);
+        assert_eq!((rlp[0], &rlp[22..]), (0xd7, &[0x81, 0x80][..]));
+    }
+
+    #[test]
+    fn create_address_vectors() {
+        for (nonce, expected) in [
+            (0, "cd234a471b72ba2f1ccf0a70fcaba648a5eecd8d"),
+            (1, "343c43a37d37dff08ae8c4a11544c718abb4fcf8"),
+            (0x7f, "06d9a77f5e4b311bae8d559db9cdb4df94104aa0"),
+            (0x80, "08e190dcb7b73f5fcdabb43e102215c83659a76d"),
+        ] {
+            assert_eq!(hex::encode(create_address(&SENDER, nonce)), expected);
+        }
+    }
+}
@@ -0,0 +1,66 @@
+use primitive_types::U256;
+use core::cmp::Ordering;
+
+// Two's complement view of a U256 for the signed opcodes.
+// The most negative value, -2^255, has no positive counterpart: negating it
+// wraps back to itself, which is exactly what SDIV's MIN / -1 case needs
+
+pub(crate) fn is_negative(value: U256) -> bool {
+    value.bit(255)
+}
+
+pub(crate) fn neg(value: U256) -> U256 {
+    (!value).overflowing_add(U256::from(1)).0
+}
+
+// Magnitude as an unsigned word; -2^255 maps to 2^255
+pub(crate) fn abs(value: U256) -> U256 {
+    if is_negative(value) { neg(value) } else { value }
+}
+
+// With differing signs the negative operand is smaller, otherwise the
+// unsigned ordering of two's complement words matches the signed one
+pub(crate) fn to_signed_cmp(a: U256, b: U256) -> Ordering {
+    match (is_negative(a), is_negative(b)) {
+        (true, false) => Ordering::Less,
+        (false, true) => Ordering::Greater,
+        _ => a.cmp(&b),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MIN_I256: U256 = U256([0, 0, 0, 1 << 63]);
+
+    #[test]
+    fn sign_bit_decides_negativity() {
+        assert!(is_negative(MIN_I256));
+        assert!(is_negative(U256::MAX));
+        assert!(!is_negative(U256::zero()));
+        assert!(!is_negative(MIN_I256 - 1));
+    }
+
+    #[test]
+    fn neg_is_twos_complement() {
+        assert_eq!(neg(U256::one()), U256::MAX);
+        assert_eq!(neg(U256::MAX), U256::one());
+        assert_eq!(neg(U256::zero()), U256::zero());
+        // MIN has no positive counterpart
+        assert_eq!(neg(MIN_I256), MIN_I256);
+        assert_eq!(abs(MIN_I256), MIN_I256);
+        assert_eq!(abs(neg(U256::from(5))), U256::from(5));
+    }
+
+    #[test]
+    fn signed_ordering() {
+        let minus_one = U256::MAX;
+        assert_eq!(to_signed_cmp(minus_one, U256::zero()), Ordering::Less);
+        assert_eq!(to_signed_cmp(U256::zero(), minus_one), Ordering::Greater);
+        assert_eq!(to_signed_cmp(MIN_I256, minus_one), Ordering::Less);
+        assert_eq!(to_signed_cmp(MIN_I256, MIN_I256 - 1), Ordering::Less);
+        assert_eq!(to_signed_cmp(MIN_I256, MIN_I256), Ordering::Equal);
+        assert_eq!(to_signed_cmp(U256::from(3), U256::from(2)), Ordering::Greater);
+    }
+}
@@ -1,6 +1,9 @@
 use primitive_types::U256; 
-use constants::MAX_DEPTH;
+use crate::evm::constants::MAX_DEPTH;
+use core::fmt;
+use alloc::vec::Vec;
 
+#[derive(Debug)]
 pub enum StackError {
     StackOverflow,
     StackUnderflow, 
@@ -8,20 +11,64 @@ pub enum StackError {
     InvalidStackItem
 }
 
+impl fmt::Display for StackError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StackError::StackOverflow => write!(f, "stack overflow"),
+            StackError::StackUnderflow => write!(f, "stack underflow"),
+            StackError::IndexError => write!(f, "stack index out of range"),
+            StackError::InvalidStackItem => write!(f, "invalid stack item"),
+        }
+    }
+}
+
+impl core::error::Error for StackError {}
+
+#[derive(Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Stack {
     data: Vec<U256>, 
     max_depth: usize, 
 }
 
+impl Default for Stack {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// Words listed from the top of the stack down, in hex
+impl fmt::Debug for Stack {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "[")?;
+        for (i, value) in self.iter().enumerate() {
+            if i > 0 {
+                write!(f, ", ")?;
+            }
+            write!(f, "{:#x}", value)?;
+        }
+        write!(f, "]")
+    }
+}
+
 impl Stack {
 
     pub fn new() -> Self {
+        Self::with_max_depth(MAX_DEPTH)
+    }
+
+    // A stack that overflows once it holds more than max_depth items
+    pub fn with_max_depth(max_depth: usize) -> Self {
         Stack {
             data: Vec::new(), 
-            max_depth: MAX_DEPTH,
+            max_depth,
         }
     }
 
+    pub fn max_depth(&self) -> usize {
+        self.max_depth
+    }
+
     pub fn push(&mut self, value: U256) -> Result<(), StackError> {
 
         if self.data.len() >= self.max_depth {
@@ -39,6 +86,20 @@ impl Stack {
         Ok(self.data.pop().unwrap()) 
     }
 
+    // Push up to 32 bytes as a big-endian word, so shorter values such as
+    // addresses are left-padded with zeros
+    pub fn push_bytes(&mut self, bytes: &[u8]) -> Result<(), StackError> {
+        if bytes.len() > 32 {
+            return Err(StackError::InvalidStackItem);
+        }
+        self.push(U256::from_big_endian(bytes))
+    }
+
+    // Pop the top item as its 32 big-endian bytes
+    pub fn pop_bytes(&mut self) -> Result<[u8; 32], StackError> {
+        Ok(self.pop()?.to_big_endian())
+    }
+
     pub fn peek(&self, index: usize) -> Result<U256, StackError> {
         if index >= self.data.len() {
             return Err(StackError::IndexError);
@@ -47,13 +108,151 @@ impl Stack {
         Ok(self.data[self.data.len() - 1 - index])
     }
 
+    // Push a copy of the item n positions below the top (0 copies the top)
+    pub fn dup(&mut self, n: usize) -> Result<(), StackError> {
+        let value = self.peek(n)?;
+        self.push(value)
+    }
+
+    // Overwrite the top item in place, for operations that consume one
+    // value and produce one
+    pub fn replace_top(&mut self, value: U256) -> Result<(), StackError> {
+        let top = self.data.last_mut().ok_or(StackError::StackUnderflow)?;
+        *top = value;
+        Ok(())
+    }
+
+    // Items from the top of the stack down
+    pub fn iter(&self) -> impl Iterator<Item = &U256> {
+        self.data.iter().rev()
+    }
+
+    // Items in storage order, bottom of the stack first
+    pub fn as_slice(&self) -> &[U256] {
+        &self.data
+    }
+
+    // Drop items from the top until at most len remain
+    pub fn truncate(&mut self, len: usize) {
+        self.data.truncate(len);
+    }
+
+    pub fn len(&self) -> usize {
+        self.data.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+
+    // Exchange the top item with the one n positions below it (1 <= n)
     pub fn swap(&mut self, n: usize) -> Result<(), StackError> {
+        // Swapping the top with itself would be a silent no-op, so reject it
+        if n == 0 {
+            return Err(StackError::IndexError);
+        }
         if n + 1 > self.data.len() {
-            return Err(StackError::StackOverflow); 
+            return Err(StackError::StackUnderflow); 
         }
 
-        self.data.swap(self.data.len() - 1, self.data.len() - 1 - n);
+        let top = self.data.len() - 1;
+        self.data.swap(top, top - n);
         Ok(())
     }
 
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::format;
+
+    #[test]
+    fn swap_needs_two_items_and_a_nonzero_depth() {
+        let mut stack = Stack::new();
+        stack.push(U256::one()).unwrap();
+        assert!(matches!(stack.swap(1), Err(StackError::StackUnderflow)));
+        assert!(matches!(stack.swap(0), Err(StackError::IndexError)));
+        stack.push(U256::from(2)).unwrap();
+        stack.swap(1).unwrap();
+        assert_eq!(stack.peek(0).unwrap(), U256::one());
+        assert_eq!(stack.peek(1).unwrap(), U256::from(2));
+    }
+
+    #[test]
+    fn dup_copies_from_any_depth_until_full() {
+        let mut stack = Stack::new();
+        assert!(matches!(stack.dup(0), Err(StackError::IndexError)));
+        stack.push(U256::one()).unwrap();
+        stack.dup(0).unwrap();
+        assert_eq!(stack.len(), 2);
+        assert_eq!(stack.peek(0).unwrap(), U256::one());
+
+        for i in 2..20 {
+            stack.push(U256::from(i)).unwrap();
+        }
+        stack.dup(15).unwrap();
+        assert_eq!(stack.peek(0).unwrap(), U256::from(4));
+
+        while stack.len() < MAX_DEPTH {
+            stack.push(U256::zero()).unwrap();
+        }
+        assert!(matches!(stack.dup(0), Err(StackError::StackOverflow)));
+        assert_eq!(stack.len(), MAX_DEPTH);
+    }
+
+    #[test]
+    fn debug_lists_words_from_the_top() {
+        let mut stack = Stack::new();
+        assert_eq!(format!("{:?}", stack), "[]");
+        stack.push(U256::one()).unwrap();
+        stack.push(U256::from(0xff)).unwrap();
+        assert_eq!(format!("{:?}", stack), "[0xff, 0x1]");
+    }
+
+    #[test]
+    fn replace_top_overwrites_in_place() {
+        let mut stack = Stack::new();
+        assert!(matches!(stack.replace_top(U256::one()), Err(StackError::StackUnderflow)));
+        stack.push(U256::one()).unwrap();
+        stack.push(U256::from(2)).unwrap();
+        stack.replace_top(U256::from(9)).unwrap();
+        assert_eq!(stack.as_slice(), &[U256::one(), U256::from(9)]);
+    }
+
+    #[test]
+    fn iter_starts_at_the_top() {
+        let mut stack = Stack::new();
+        for i in 1..=3 {
+            stack.push(U256::from(i)).unwrap();
+        }
+        let top_first: Vec<U256> = stack.iter().copied().collect();
+        assert_eq!(top_first, [U256::from(3), U256::from(2), U256::one()]);
+        assert_eq!(stack.as_slice(), &[U256::one(), U256::from(2), U256::from(3)]);
+    }
+
+    #[test]
+    fn overflow_follows_the_configured_depth() {
+        let mut stack = Stack::with_max_depth(3);
+        for i in 0..3 {
+            stack.push(U256::from(i)).unwrap();
+        }
+        assert!(matches!(stack.push(U256::from(3)), Err(StackError::StackOverflow)));
+        assert_eq!(stack.len(), 3);
+        assert_eq!(Stack::new().max_depth(), MAX_DEPTH);
+    }
+
+    #[test]
+    fn address_bytes_round_trip() {
+        let mut stack = Stack::new();
+        let address = [0xab; 20];
+        stack.push_bytes(&address).unwrap();
+        assert_eq!(stack.peek(0).unwrap(), U256::from_big_endian(&address));
+        let word = stack.pop_bytes().unwrap();
+        assert_eq!(word[..12], [0; 12]);
+        assert_eq!(word[12..], address);
+
+        assert!(matches!(stack.push_bytes(&[1; 33]), Err(StackError::InvalidStackItem)));
+        assert!(matches!(stack.pop_bytes(), Err(StackError::StackUnderflow)));
+    }
+}
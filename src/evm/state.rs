@@ -0,0 +1,129 @@
+use crate::evm::context::Address;
+use crate::evm::storage::Storage;
+use primitive_types::U256;
+use crate::evm::collections::Map;
+use alloc::vec::Vec;
+
+#[derive(Clone, Default)]
+pub struct Account {
+    pub balance: U256,
+    pub nonce: u64,
+    pub code: Vec<u8>,
+    pub storage: Storage,
+}
+
+impl Account {
+
+    // No code, nonce or balance. Empty accounts are treated the same as
+    // accounts that do not exist (EIP-161)
+    pub fn is_empty(&self) -> bool {
+        self.code.is_empty() && self.nonce == 0 && self.balance.is_zero()
+    }
+}
+
+// Every account the execution can see, keyed by address
+#[derive(Clone, Default)]
+pub struct WorldState {
+    accounts: Map<Address, Account>,
+}
+
+impl WorldState {
+
+    pub fn new() -> Self {
+        WorldState {
+            accounts: Map::new(),
+        }
+    }
+
+    pub fn account(&self, address: &Address) -> Option<&Account> {
+        self.accounts.get(address)
+    }
+
+    pub fn account_mut(&mut self, address: &Address) -> Option<&mut Account> {
+        self.accounts.get_mut(address)
+    }
+
+    // Look up an account, inserting an empty one if it does not exist yet
+    pub fn get_or_create(&mut self, address: Address) -> &mut Account {
+        self.accounts.entry(address).or_default()
+    }
+
+    pub fn exists(&self, address: &Address) -> bool {
+        self.accounts.contains_key(address)
+    }
+
+    pub fn remove_account(&mut self, address: &Address) -> Option<Account> {
+        self.accounts.remove(address)
+    }
+
+    // Missing accounts read as empty: zero balance, zero nonce and no code
+    pub fn balance(&self, address: &Address) -> U256 {
+        self.accounts.get(address).map_or(U256::zero(), |account| account.balance)
+    }
+
+    pub fn set_balance(&mut self, address: Address, balance: U256) {
+        self.get_or_create(address).balance = balance;
+    }
+
+    pub fn nonce(&self, address: &Address) -> u64 {
+        self.accounts.get(address).map_or(0, |account| account.nonce)
+    }
+
+    pub fn set_nonce(&mut self, address: Address, nonce: u64) {
+        self.get_or_create(address).nonce = nonce;
+    }
+
+    pub fn code(&self, address: &Address) -> &[u8] {
+        self.accounts.get(address).map_or(&[], |account| account.code.as_slice())
+    }
+
+    pub fn set_code(&mut self, address: Address, code: Vec<u8>) {
+        self.get_or_create(address).code = code;
+    }
+
+    // Move an account's storage out, leaving it empty until set_storage puts
+    // it back; used to hand storage to the frame executing that account
+    pub fn take_storage(&mut self, address: &Address) -> Storage {
+        self.accounts.get_mut(address).map(|account| core::mem::take(&mut account.storage)).unwrap_or_default()
+    }
+
+    pub fn set_storage(&mut self, address: Address, storage: Storage) {
+        self.get_or_create(address).storage = storage;
+    }
+
+    // Move value between accounts; the caller checks the sender can afford it
+    pub fn transfer(&mut self, from: Address, to: Address, value: U256) {
+        let sender = self.get_or_create(from);
+        sender.balance = sender.balance.saturating_sub(value);
+        let recipient = self.get_or_create(to);
+        recipient.balance = recipient.balance.saturating_add(value);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec;
+
+    #[test]
+    fn accounts_spring_into_existence_on_write() {
+        let mut state = WorldState::new();
+        let (a, b) = ([1u8; 20], [2u8; 20]);
+        assert!(!state.exists(&a));
+        assert_eq!(state.balance(&a), U256::zero());
+        assert_eq!(state.nonce(&a), 0);
+        assert!(state.code(&a).is_empty());
+
+        state.get_or_create(a).balance = U256::from(100);
+        state.set_nonce(a, 3);
+        state.set_code(b, vec![0x00]);
+        state.transfer(a, b, U256::from(40));
+        assert_eq!(state.balance(&a), U256::from(60));
+        assert_eq!(state.balance(&b), U256::from(40));
+        assert_eq!(state.nonce(&a), 3);
+        assert_eq!(state.code(&b), [0x00]);
+
+        assert!(state.remove_account(&a).is_some());
+        assert!(!state.exists(&a));
+    }
+}
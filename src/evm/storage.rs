@@ -1,31 +1,50 @@
 use primitive_types::U256; 
-use std::collections::HashMap;
+use crate::evm::collections::Map;
+use crate::evm::context::Address;
+use core::fmt;
 
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Storage {
-    data: HashMap<U256, U256>, 
+    data: Map<U256, U256>, 
 }
 
+// A saved copy of storage that can later be restored with revert_to
+#[derive(Clone)]
+pub struct StorageSnapshot {
+    data: Map<U256, U256>, 
+}
+
+#[derive(Debug)]
 pub enum StorageError {
     StorageAccessError, 
 }
 
+impl fmt::Display for StorageError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StorageError::StorageAccessError => write!(f, "storage access error"),
+        }
+    }
+}
+
+impl core::error::Error for StorageError {}
+
+
+impl Default for Storage {
+    fn default() -> Self {
+        Self::new()
+    }
+}
 
 impl Storage {
 
     pub fn new() -> Self {
         Storage {
-            data: HashMap::new(), 
+            data: Map::new(), 
         }
     }
 
-    pub fn load_storage(&self, storage: &Storage, slot: U256) -> Result<U256, StorageError> {
-        storage.load(slot)
-    }
-
-    pub fn store_storage(&mut self, storage: &mut Storage, slot: U256, value: U256) -> Result<(), StorageError> {
-        storage.store(slot, value) 
-    }
-
     pub fn store(&mut self, key: U256, value: U256) -> Result<(), StorageError> {
         self.data.insert(key, value);
         Ok(())
@@ -38,4 +57,103 @@ impl Storage {
     pub fn contains(&self, key: U256) -> bool {
         self.data.contains_key(&key)
     }
+
+    // Forget a slot entirely, so it reads as zero and is no longer listed
+    pub fn remove(&mut self, key: U256) {
+        self.data.remove(&key);
+    }
+
+    // Every written slot as (key, value), in no particular order
+    pub fn iter(&self) -> impl Iterator<Item = (&U256, &U256)> {
+        self.data.iter()
+    }
+
+    pub fn snapshot(&self) -> StorageSnapshot {
+        StorageSnapshot {
+            data: self.data.clone(),
+        }
+    }
+
+    // Discard every write made since the snapshot was taken
+    pub fn revert_to(&mut self, snapshot: StorageSnapshot) {
+        self.data = snapshot.data;
+    }
+}
+
+// Transient storage (EIP-1153): each contract has its own slots, which
+// last for one transaction
+#[derive(Clone, Debug, PartialEq)]
+pub struct TransientStorage {
+    accounts: Map<Address, Storage>, 
+}
+
+impl Default for TransientStorage {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl TransientStorage {
+
+    pub fn new() -> Self {
+        TransientStorage {
+            accounts: Map::new(), 
+        }
+    }
+
+    pub fn store(&mut self, address: Address, key: U256, value: U256) -> Result<(), StorageError> {
+        self.account_mut(address).store(key, value)
+    }
+
+    pub fn load(&self, address: &Address, key: U256) -> Result<U256, StorageError> {
+        self.accounts.get(address).map_or(Ok(U256::zero()), |storage| storage.load(key))
+    }
+
+    pub fn contains(&self, address: &Address, key: U256) -> bool {
+        self.accounts.get(address).is_some_and(|storage| storage.contains(key))
+    }
+
+    // One contract's slots, empty if it has not written any yet
+    pub fn account_mut(&mut self, address: Address) -> &mut Storage {
+        self.accounts.entry(address).or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn load_reads_what_store_wrote() {
+        let mut storage = Storage::new();
+        assert_eq!(storage.load(U256::one()).unwrap(), U256::zero());
+        assert!(!storage.contains(U256::one()));
+        storage.store(U256::one(), U256::from(5)).unwrap();
+        assert_eq!(storage.load(U256::one()).unwrap(), U256::from(5));
+        assert!(storage.contains(U256::one()));
+    }
+
+    #[test]
+    fn revert_restores_the_snapshot() {
+        let mut storage = Storage::new();
+        storage.store(U256::one(), U256::from(5)).unwrap();
+        let snapshot = storage.snapshot();
+        storage.store(U256::one(), U256::from(6)).unwrap();
+        storage.store(U256::from(2), U256::from(7)).unwrap();
+        storage.revert_to(snapshot);
+        assert_eq!(storage.load(U256::one()).unwrap(), U256::from(5));
+        assert!(!storage.contains(U256::from(2)));
+    }
+
+    #[test]
+    fn transient_slots_are_kept_per_contract() {
+        let (a, b) = ([1u8; 20], [2u8; 20]);
+        let mut transient = TransientStorage::new();
+        transient.store(a, U256::zero(), U256::from(42)).unwrap();
+        assert_eq!(transient.load(&a, U256::zero()).unwrap(), U256::from(42));
+        assert_eq!(transient.load(&b, U256::zero()).unwrap(), U256::zero());
+        assert!(!transient.contains(&b, U256::zero()));
+        transient.store(b, U256::zero(), U256::from(7)).unwrap();
+        assert_eq!(transient.load(&a, U256::zero()).unwrap(), U256::from(42));
+    }
 }
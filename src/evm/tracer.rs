@@ -0,0 +1,121 @@
+use crate::evm::context::ExecutionContext;
+#[cfg(feature = "std")]
+use crate::evm::disasm::mnemonic;
+use crate::evm::opcodes::InstructionError;
+#[cfg(feature = "std")]
+use alloc::{format, string::String, vec::Vec};
+#[cfg(feature = "std")]
+use std::io::Write;
+
+// Hooks invoked by the run loop around each instruction. Both default to
+// doing nothing so tracers only implement what they need
+pub trait Tracer {
+    fn before_step(&mut self, _ctx: &ExecutionContext) {}
+
+    fn after_step(&mut self, _ctx: &ExecutionContext, _result: &Result<(), InstructionError>) {}
+}
+
+// Writes one EIP-3155 style JSON object per executed instruction. Needs std
+// for its writer
+#[cfg(feature = "std")]
+pub struct JsonTracer<W: Write> {
+    writer: W,
+    // State captured before the current step, completed once its cost is known
+    pending: Option<PendingStep>,
+}
+
+#[cfg(feature = "std")]
+struct PendingStep {
+    pc: usize,
+    opcode: u8,
+    gas: u64,
+    stack: Vec<String>,
+    // EIP-3155 counts the outermost frame as depth 1
+    depth: usize,
+}
+
+#[cfg(feature = "std")]
+impl<W: Write> JsonTracer<W> {
+
+    pub fn new(writer: W) -> Self {
+        JsonTracer {
+            writer,
+            pending: None,
+        }
+    }
+
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+#[cfg(feature = "std")]
+impl<W: Write> Tracer for JsonTracer<W> {
+    fn before_step(&mut self, ctx: &ExecutionContext) {
+        // EIP-3155 lists the stack bottom first
+        let mut stack: Vec<String> = (0..)
+            .map_while(|i| ctx.stack().peek(i).ok())
+            .map(|word| format!("\"{:#x}\"", word))
+            .collect();
+        stack.reverse();
+        self.pending = Some(PendingStep {
+            pc: ctx.pc(),
+            opcode: ctx.code()[ctx.pc()],
+            gas: ctx.gas().remaining(),
+            stack,
+            depth: ctx.depth() + 1,
+        });
+    }
+
+    fn after_step(&mut self, ctx: &ExecutionContext, _result: &Result<(), InstructionError>) {
+        let Some(step) = self.pending.take() else {
+            return;
+        };
+        let gas_cost = step.gas.saturating_sub(ctx.gas().remaining());
+        // Tracing must not disturb execution, so write failures are ignored
+        let _ = writeln!(
+            self.writer,
+            "{{\"pc\":{},\"op\":{},\"gas\":\"{:#x}\",\"gasCost\":\"{:#x}\",\"stack\":[{}],\"depth\":{},\"opName\":\"{}\"}}",
+            step.pc,
+            step.opcode,
+            step.gas,
+            gas_cost,
+            step.stack.join(","),
+            step.depth,
+            mnemonic(step.opcode).unwrap_or("INVALID"),
+        );
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::*;
+    use crate::evm::interpreter::run_with_tracer;
+    use crate::evm::opcodes::{PUSH1, STOP};
+    use alloc::vec;
+
+    fn trace(depth: usize) -> String {
+        let mut ctx = ExecutionContext::new([0u8; 20], vec![PUSH1, 1, STOP], vec![]);
+        ctx.set_depth(depth);
+        let mut tracer = JsonTracer::new(Vec::new());
+        run_with_tracer(&mut ctx, &mut tracer).unwrap();
+        String::from_utf8(tracer.into_inner()).unwrap()
+    }
+
+    #[test]
+    fn json_lines_per_step() {
+        let output = trace(0);
+        let lines: Vec<&str> = output.lines().collect();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(
+            lines[0],
+            "{\"pc\":0,\"op\":96,\"gas\":\"0x1c9c380\",\"gasCost\":\"0x3\",\"stack\":[],\"depth\":1,\"opName\":\"PUSH1\"}"
+        );
+        assert!(lines[1].contains("\"stack\":[\"0x1\"]"));
+    }
+
+    #[test]
+    fn depth_follows_the_frame() {
+        assert!(trace(2).lines().all(|line| line.contains("\"depth\":3")));
+    }
+}
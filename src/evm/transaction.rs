@@ -0,0 +1,193 @@
+use crate::evm::context::Address;
+use crate::evm::env::TxEnv;
+use crate::evm::hash::keccak256;
+use crate::evm::precompiles::recover_address;
+use crate::evm::rlp::{rlp_decode, rlp_encode_bytes, rlp_encode_list, rlp_encode_u256, RlpError, RlpItem};
+use alloc::vec::Vec;
+use core::fmt;
+use primitive_types::U256;
+
+// A signed legacy transaction, the untyped kind that predates EIP-2718
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Transaction {
+    pub nonce: u64,
+    pub gas_price: U256,
+    pub gas_limit: u64,
+    // None for contract creation
+    pub to: Option<Address>,
+    pub value: U256,
+    pub data: Vec<u8>,
+    // 27 or 28, or chain_id * 2 + 35 or 36 under EIP-155
+    pub v: u64,
+    pub r: U256,
+    pub s: U256,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum TransactionError {
+    Rlp(RlpError),
+    // Well-formed RLP, but not the nine fields of a legacy transaction
+    InvalidFields,
+    // Typed transactions (EIP-2718) are not supported
+    UnsupportedType,
+    // v does not name a recovery id, or no key produced the signature
+    InvalidSignature,
+}
+
+impl fmt::Display for TransactionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TransactionError::Rlp(err) => write!(f, "invalid transaction rlp: {}", err),
+            TransactionError::InvalidFields => write!(f, "invalid transaction fields"),
+            TransactionError::UnsupportedType => write!(f, "unsupported transaction type"),
+            TransactionError::InvalidSignature => write!(f, "invalid transaction signature"),
+        }
+    }
+}
+
+impl core::error::Error for TransactionError {
+    fn source(&self) -> Option<&(dyn core::error::Error + 'static)> {
+        match self {
+            TransactionError::Rlp(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl Transaction {
+
+    // Decode a raw signed transaction, as sent to eth_sendRawTransaction
+    pub fn decode(rlp: &[u8]) -> Result<Self, TransactionError> {
+        // Typed transactions start with their type byte, which is never a
+        // list prefix
+        if rlp.first().is_some_and(|&byte| byte < 0xc0) {
+            return Err(TransactionError::UnsupportedType);
+        }
+        let RlpItem::List(items) = rlp_decode(rlp).map_err(TransactionError::Rlp)? else {
+            return Err(TransactionError::InvalidFields);
+        };
+        let [nonce, gas_price, gas_limit, to, value, data, v, r, s] = items.as_slice() else {
+            return Err(TransactionError::InvalidFields);
+        };
+        let to = match bytes(to)? {
+            [] => None,
+            address => Some(Address::try_from(address).map_err(|_| TransactionError::InvalidFields)?),
+        };
+        Ok(Transaction {
+            nonce: to_u64(uint(nonce)?)?,
+            gas_price: uint(gas_price)?,
+            gas_limit: to_u64(uint(gas_limit)?)?,
+            to,
+            value: uint(value)?,
+            data: bytes(data)?.to_vec(),
+            v: to_u64(uint(v)?)?,
+            r: uint(r)?,
+            s: uint(s)?,
+        })
+    }
+
+    // The chain the signature is bound to (EIP-155), or None if it predates
+    // replay protection and is valid on every chain
+    pub fn chain_id(&self) -> Option<u64> {
+        (self.v >= 35).then(|| (self.v - 35) / 2)
+    }
+
+    // The hash that was signed: the RLP of the first six fields, followed
+    // under EIP-155 by the chain id and two empty fields
+    pub fn signing_hash(&self) -> [u8; 32] {
+        let mut fields = Vec::from([
+            rlp_encode_u256(U256::from(self.nonce)),
+            rlp_encode_u256(self.gas_price),
+            rlp_encode_u256(U256::from(self.gas_limit)),
+            rlp_encode_bytes(self.to.as_ref().map_or(&[], |to| to.as_slice())),
+            rlp_encode_u256(self.value),
+            rlp_encode_bytes(&self.data),
+        ]);
+        if let Some(chain_id) = self.chain_id() {
+            fields.push(rlp_encode_u256(U256::from(chain_id)));
+            fields.push(rlp_encode_u256(U256::zero()));
+            fields.push(rlp_encode_u256(U256::zero()));
+        }
+        keccak256(&rlp_encode_list(&fields))
+    }
+
+    // Recover the address that signed the transaction, with the same rules
+    // as the ECRECOVER precompile
+    pub fn sender(&self) -> Result<Address, TransactionError> {
+        let recovery_id = match self.v {
+            27 | 28 => self.v - 27,
+            35.. => (self.v - 35) % 2,
+            _ => return Err(TransactionError::InvalidSignature),
+        };
+        let mut input = [0u8; 128];
+        input[..32].copy_from_slice(&self.signing_hash());
+        input[63] = 27 + recovery_id as u8;
+        input[64..96].copy_from_slice(&self.r.to_big_endian());
+        input[96..].copy_from_slice(&self.s.to_big_endian());
+        recover_address(&input).ok_or(TransactionError::InvalidSignature)
+    }
+
+    // The environment for executing this transaction, sent by its signer
+    pub fn tx_env(&self) -> Result<TxEnv, TransactionError> {
+        let sender = self.sender()?;
+        Ok(TxEnv {
+            caller: sender,
+            origin: sender,
+            value: self.value,
+            gas_price: self.gas_price,
+        })
+    }
+}
+
+fn bytes<'a>(item: &RlpItem<'a>) -> Result<&'a [u8], TransactionError> {
+    match item {
+        RlpItem::Bytes(bytes) => Ok(bytes),
+        RlpItem::List(_) => Err(TransactionError::InvalidFields),
+    }
+}
+
+// Integers are at most a word and, like all RLP integers, have no leading zeros
+fn uint(item: &RlpItem) -> Result<U256, TransactionError> {
+    let bytes = bytes(item)?;
+    if bytes.len() > 32 || bytes.first() == Some(&0) {
+        return Err(TransactionError::InvalidFields);
+    }
+    Ok(U256::from_big_endian(bytes))
+}
+
+fn to_u64(value: U256) -> Result<u64, TransactionError> {
+    u64::try_from(value).map_err(|_| TransactionError::InvalidFields)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // The example transaction from EIP-155, signed with the key 0x4646..46
+    const EIP155_EXAMPLE: &str = "f86c098504a817c800825208943535353535353535353535353535353535353535880de0b6b3a76400008025a028ef61340bd939bc2195fe537567866003e1a15d3c71ff63e1590620aa636276a067cbe9d8997f761aecb703304b3800ccf555c9f3dc64214b297fb1966a3b6d83";
+
+    #[test]
+    fn recovers_the_known_sender() {
+        let tx = Transaction::decode(&hex::decode(EIP155_EXAMPLE).unwrap()).unwrap();
+        assert_eq!(tx.nonce, 9);
+        assert_eq!(tx.gas_limit, 21000);
+        assert_eq!(tx.chain_id(), Some(1));
+        assert_eq!(hex::encode(tx.signing_hash()), "daf5a779ae972f972197303d7b574746c7ef83eadac0f2791ad23db92e4c8e53");
+        let sender = tx.sender().unwrap();
+        assert_eq!(hex::encode(sender), "9d8a62f656a8d1615c1294fd71e9cfb3e4855a4f");
+        let env = tx.tx_env().unwrap();
+        assert_eq!((env.caller, env.origin), (sender, sender));
+        assert_eq!(env.gas_price, U256::from(20_000_000_000u64));
+    }
+
+    #[test]
+    fn bad_signatures_and_encodings_are_rejected() {
+        let raw = hex::decode(EIP155_EXAMPLE).unwrap();
+        let mut tx = Transaction::decode(&raw).unwrap();
+        tx.v = 30;
+        assert_eq!(tx.sender(), Err(TransactionError::InvalidSignature));
+        assert!(matches!(Transaction::decode(&[0x02, 0xc0]), Err(TransactionError::UnsupportedType)));
+        assert!(matches!(Transaction::decode(&[0xc0]), Err(TransactionError::InvalidFields)));
+        assert!(matches!(Transaction::decode(&raw[..raw.len() - 1]), Err(TransactionError::Rlp(_))));
+    }
+}
@@ -0,0 +1,8 @@
+#![cfg_attr(not(any(feature = "std", test)), no_std)]
+
+extern crate alloc;
+
+pub mod evm;
+
+#[cfg(feature = "vmtests")]
+pub mod testing;
@@ -0,0 +1,223 @@
+use crate::evm::context::{Address, ExecutionContext};
+use crate::evm::env::BlockEnv;
+use crate::evm::hardfork::Hardfork;
+use crate::evm::interpreter::run;
+use crate::evm::state::WorldState;
+use crate::evm::storage::Storage;
+use primitive_types::U256;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+
+// One test from the ethereum/tests VMTests suite. Every number, address and
+// byte string in the format is a 0x-prefixed hex string. A test without
+// `post` and `gas` expects execution to fail
+#[derive(Debug, Deserialize)]
+pub struct VmTest {
+    pub env: VmTestEnv,
+    pub exec: VmTestExec,
+    pub pre: BTreeMap<String, VmTestAccount>,
+    pub post: Option<BTreeMap<String, VmTestAccount>>,
+    // Gas left over after execution
+    pub gas: Option<String>,
+    pub out: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct VmTestEnv {
+    pub current_coinbase: String,
+    pub current_difficulty: String,
+    pub current_gas_limit: String,
+    pub current_number: String,
+    pub current_timestamp: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct VmTestExec {
+    pub address: String,
+    pub caller: String,
+    pub code: String,
+    pub data: String,
+    pub gas: String,
+    pub gas_price: String,
+    pub origin: String,
+    pub value: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct VmTestAccount {
+    pub balance: String,
+    pub code: String,
+    pub nonce: String,
+    pub storage: BTreeMap<String, String>,
+}
+
+// Parse a VMTests file: a JSON object of tests keyed by name
+pub fn load_vm_tests(json: &str) -> Result<BTreeMap<String, VmTest>, serde_json::Error> {
+    serde_json::from_str(json)
+}
+
+// Run a test and check its outcome, describing the first mismatch found.
+// The suite was filled under Homestead rules, so that is what it runs with
+pub fn run_vm_test(test: &VmTest) -> Result<(), String> {
+    let address = parse_address(&test.exec.address)?;
+    let mut state = WorldState::new();
+    for (account_address, account) in &test.pre {
+        let account_address = parse_address(account_address)?;
+        state.set_balance(account_address, parse_u256(&account.balance)?);
+        state.set_nonce(account_address, parse_u64(&account.nonce)?);
+        state.set_code(account_address, parse_bytes(&account.code)?);
+        state.set_storage(account_address, parse_storage(&account.storage)?);
+    }
+    
+    let block = BlockEnv {
+        coinbase: parse_address(&test.env.current_coinbase)?,
+        timestamp: parse_u256(&test.env.current_timestamp)?,
+        number: parse_u256(&test.env.current_number)?,
+        gas_limit: parse_u256(&test.env.current_gas_limit)?,
+        prev_randao: parse_u256(&test.env.current_difficulty)?,
+        ..BlockEnv::default()
+    };
+    let gas_limit = parse_u64(&test.exec.gas)?;
+    let mut ctx = ExecutionContext::builder()
+        .address(address)
+        .code(parse_bytes(&test.exec.code)?)
+        .calldata(parse_bytes(&test.exec.data)?)
+        .caller(parse_address(&test.exec.caller)?)
+        .origin(parse_address(&test.exec.origin)?)
+        .value(parse_u256(&test.exec.value)?)
+        .gas_price(parse_u256(&test.exec.gas_price)?)
+        .gas_limit(gas_limit)
+        .block(block)
+        .hardfork(Hardfork::Homestead)
+        .state(state)
+        .build();
+    // The executing contract's storage lives on the context while it runs
+    *ctx.storage_mut() = ctx.state_mut().take_storage(&address);
+    let result = run(&mut ctx);
+    
+    let Some(post) = &test.post else {
+        return match result {
+            Ok(()) => Err("expected execution to fail, but it succeeded".to_string()),
+            Err(_) => Ok(()),
+        };
+    };
+    result.map_err(|err| format!("execution failed: {}", err))?;
+    
+    if let Some(gas) = &test.gas {
+        let expected = parse_u64(gas)?;
+        let remaining = ctx.gas().remaining();
+        if remaining != expected {
+            return Err(format!("gas left: expected {}, got {}", expected, remaining));
+        }
+    }
+    if let Some(out) = &test.out {
+        let expected = parse_bytes(out)?;
+        if *ctx.return_data() != expected {
+            return Err(format!("output: expected 0x{}, got {}", hex::encode(&expected), ctx.return_data_hex()));
+        }
+    }
+    
+    let own_storage = std::mem::take(ctx.storage_mut());
+    ctx.state_mut().set_storage(address, own_storage);
+    for (account_address, expected) in post {
+        check_account(ctx.state(), parse_address(account_address)?, expected)?;
+    }
+    Ok(())
+}
+
+fn check_account(state: &WorldState, address: Address, expected: &VmTestAccount) -> Result<(), String> {
+    let name = format!("0x{}", hex::encode(address));
+    let balance = parse_u256(&expected.balance)?;
+    if state.balance(&address) != balance {
+        return Err(format!("{} balance: expected {:#x}, got {:#x}", name, balance, state.balance(&address)));
+    }
+    let code = parse_bytes(&expected.code)?;
+    if state.code(&address) != code.as_slice() {
+        return Err(format!("{} code differs", name));
+    }
+    
+    let expected_storage = parse_storage(&expected.storage)?;
+    let storage = state.account(&address).map(|account| account.storage.clone()).unwrap_or_default();
+    // Slots written back to zero count as absent
+    let slots = storage.iter().map(|(key, _)| *key).chain(expected_storage.iter().map(|(key, _)| *key));
+    for slot in slots {
+        let value = storage.load(slot).map_err(|err| err.to_string())?;
+        let want = expected_storage.load(slot).map_err(|err| err.to_string())?;
+        if value != want {
+            return Err(format!("{} storage[{:#x}]: expected {:#x}, got {:#x}", name, slot, want, value));
+        }
+    }
+    Ok(())
+}
+
+fn parse_u256(value: &str) -> Result<U256, String> {
+    let digits = value.strip_prefix("0x").unwrap_or(value);
+    if digits.is_empty() {
+        return Ok(U256::zero());
+    }
+    U256::from_str_radix(digits, 16).map_err(|err| format!("bad number {:?}: {}", value, err))
+}
+
+// Gas amounts and nonces are 64-bit here; larger values are rejected
+// rather than truncated
+fn parse_u64(value: &str) -> Result<u64, String> {
+    u64::try_from(parse_u256(value)?).map_err(|_| format!("number {:?} does not fit in 64 bits", value))
+}
+
+fn parse_bytes(value: &str) -> Result<Vec<u8>, String> {
+    let digits = value.strip_prefix("0x").unwrap_or(value);
+    hex::decode(digits).map_err(|err| format!("bad hex {:?}: {}", value, err))
+}
+
+fn parse_address(value: &str) -> Result<Address, String> {
+    let bytes = parse_bytes(value)?;
+    bytes.try_into().map_err(|_| format!("bad address {:?}", value))
+}
+
+fn parse_storage(slots: &BTreeMap<String, String>) -> Result<Storage, String> {
+    let mut storage = Storage::new();
+    for (key, value) in slots {
+        storage.store(parse_u256(key)?, parse_u256(value)?).map_err(|err| err.to_string())?;
+    }
+    Ok(storage)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FIXTURES: [&str; 3] = [
+        include_str!("../../fixtures/vmtests/vmArithmeticTest.json"),
+        include_str!("../../fixtures/vmtests/vmIOandFlowOperations.json"),
+        include_str!("../../fixtures/vmtests/vmSha3Test.json"),
+    ];
+
+    #[test]
+    fn fixtures_pass() {
+        for json in FIXTURES {
+            for (name, test) in load_vm_tests(json).unwrap() {
+                if let Err(err) = run_vm_test(&test) {
+                    panic!("{}: {}", name, err);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn wrong_expectation_is_reported() {
+        let json = FIXTURES[0].replace("\"0x06\"", "\"0x07\"");
+        let tests = load_vm_tests(&json).unwrap();
+        let err = run_vm_test(&tests["mul0"]).unwrap_err();
+        assert!(err.ends_with("storage[0x0]: expected 0x7, got 0x6"), "{}", err);
+    }
+
+    #[test]
+    fn oversized_gas_is_rejected() {
+        let json = FIXTURES[0].replace("\"0x0186a0\"", "\"0x010000000000000000\"");
+        let tests = load_vm_tests(&json).unwrap();
+        let err = run_vm_test(&tests["add0"]).unwrap_err();
+        assert!(err.contains("does not fit in 64 bits"), "{}", err);
+    }
+}